@@ -8,5 +8,14 @@ psql -U postgres < setup.sql
 sudo cp pg_hba.conf $(psql -U postgres -c "SHOW hba_file" -At)
 
 DATA_DIR=$(psql -U postgres -c "SHOW data_directory" -At)
+
+# Serve a certificate signed with ecdsa-with-SHA384 to test channel binding
+# with a hash other than SHA-256.
+sudo cp server-sha384.crt server-sha384.key $DATA_DIR
+sudo chown postgres $DATA_DIR/server-sha384.crt $DATA_DIR/server-sha384.key
+sudo chmod 600 $DATA_DIR/server-sha384.key
+psql -U postgres -c "ALTER SYSTEM SET ssl_cert_file = 'server-sha384.crt'"
+psql -U postgres -c "ALTER SYSTEM SET ssl_key_file = 'server-sha384.key'"
+
 PG_PID=$(sudo head -n1 $DATA_DIR/postmaster.pid)
 sudo kill -SIGHUP $PG_PID
@@ -0,0 +1,581 @@
+//! Support for libpq-style keyword/value connection strings.
+//!
+//! See the [Postgres documentation](http://www.postgresql.org/docs/current/static/libpq-connect.html#LIBPQ-CONNSTRING)
+//! for details on the format.
+use std::error::Error as StdError;
+use std::cmp;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::{Chars, FromStr};
+use std::time::Duration;
+
+use {ConnectParams, ConnectTarget, UserInfo, ChannelBinding, TargetSessionAttrs,
+     LoadBalanceHosts, SslPolicy, SslFiles, SslNegotiation, make_target, split_hosts};
+use defaults;
+use service;
+
+/// Parses a string of the form `host=localhost port=5432 user=postgres` into
+/// a `ConnectParams`.
+pub fn parse(s: &str) -> Result<ConnectParams, Box<StdError + Sync + Send>> {
+    let pairs = try!(parse_pairs(s));
+    from_pairs(pairs)
+}
+
+/// Splits a connection string into its keyword/value pairs.
+pub fn parse_pairs(s: &str) -> Result<Vec<(String, String)>, Box<StdError + Sync + Send>> {
+    let mut pairs = vec![];
+    let mut it = s.chars().peekable();
+
+    loop {
+        skip_whitespace(&mut it);
+        if it.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(&c) = it.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            key.push(c);
+            it.next();
+        }
+
+        skip_whitespace(&mut it);
+        if it.next() != Some('=') || key.is_empty() {
+            return Err(format!("missing \"=\" after \"{}\" in connection info string", key)
+                           .into());
+        }
+        skip_whitespace(&mut it);
+
+        let value = try!(parse_value(&mut it));
+        pairs.push((key, value));
+    }
+
+    Ok(pairs)
+}
+
+fn skip_whitespace(it: &mut Peekable<Chars>) {
+    while let Some(&c) = it.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        it.next();
+    }
+}
+
+fn parse_value(it: &mut Peekable<Chars>) -> Result<String, Box<StdError + Sync + Send>> {
+    let mut value = String::new();
+
+    if it.peek() == Some(&'\'') {
+        it.next();
+        loop {
+            match it.next() {
+                Some('\'') => return Ok(value),
+                Some('\\') => {
+                    match it.next() {
+                        Some(c) => value.push(c),
+                        None => break,
+                    }
+                }
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+        return Err("unterminated quoted string in connection info string".into());
+    }
+
+    while let Some(c) = it.next() {
+        match c {
+            c if c.is_whitespace() => break,
+            '\\' => {
+                if let Some(c) = it.next() {
+                    value.push(c);
+                }
+            }
+            c => value.push(c),
+        }
+    }
+
+    Ok(value)
+}
+
+/// Builds a `ConnectParams` from keyword/value pairs.
+///
+/// Later pairs override earlier ones with the same keyword. Unspecified
+/// parameters are taken from the service file and environment.
+pub fn from_pairs<I>(pairs: I) -> Result<ConnectParams, Box<StdError + Sync + Send>>
+    where I: IntoIterator<Item = (String, String)>
+{
+    let mut builder = Builder::new();
+    for (key, value) in pairs {
+        try!(builder.set(key, value));
+    }
+    builder.build()
+}
+
+/// Accumulates explicitly specified connection parameters.
+pub struct Builder {
+    host: Option<Vec<ConnectTarget>>,
+    port: Option<Vec<Option<u16>>>,
+    user: Option<String>,
+    password: Option<String>,
+    database: Option<String>,
+    options: Vec<(String, String)>,
+    channel_binding: Option<ChannelBinding>,
+    ssl_mode: Option<SslPolicy>,
+    ssl_root_cert: Option<PathBuf>,
+    ssl_cert: Option<PathBuf>,
+    ssl_key: Option<PathBuf>,
+    ssl_password: Option<String>,
+    ssl_crl: Option<PathBuf>,
+    ssl_negotiation: Option<SslNegotiation>,
+    target_session_attrs: Option<TargetSessionAttrs>,
+    load_balance_hosts: Option<LoadBalanceHosts>,
+    service: Option<String>,
+    connect_timeout: Option<Option<Duration>>,
+    keepalives: Option<bool>,
+    keepalives_idle: Option<Option<Duration>>,
+    keepalives_interval: Option<Option<Duration>>,
+    keepalives_count: Option<Option<u32>>,
+    tcp_user_timeout: Option<Option<Duration>>,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder {
+            host: None,
+            port: None,
+            user: None,
+            password: None,
+            database: None,
+            options: vec![],
+            channel_binding: None,
+            ssl_mode: None,
+            ssl_root_cert: None,
+            ssl_cert: None,
+            ssl_key: None,
+            ssl_password: None,
+            ssl_crl: None,
+            ssl_negotiation: None,
+            target_session_attrs: None,
+            load_balance_hosts: None,
+            service: None,
+            connect_timeout: None,
+            keepalives: None,
+            keepalives_idle: None,
+            keepalives_interval: None,
+            keepalives_count: None,
+            tcp_user_timeout: None,
+        }
+    }
+
+    /// Sets the parameter corresponding to a connection string keyword.
+    pub fn set(&mut self, key: String, value: String) -> Result<(), Box<StdError + Sync + Send>> {
+        match &*key {
+            "host" => self.host = Some(try!(parse_hosts(&value))),
+            "port" => self.port = Some(try!(parse_ports(&value))),
+            "user" => self.user = Some(value),
+            "password" => self.password = Some(value),
+            "dbname" => self.database = Some(value),
+            "channel_binding" => self.channel_binding = Some(try!(ChannelBinding::parse(&value))),
+            "target_session_attrs" => {
+                self.target_session_attrs = Some(try!(TargetSessionAttrs::parse(&value)))
+            }
+            "load_balance_hosts" => {
+                self.load_balance_hosts = Some(try!(LoadBalanceHosts::parse(&value)))
+            }
+            "service" => self.service = Some(value),
+            "connect_timeout" => self.connect_timeout = Some(try!(parse_connect_timeout(&value))),
+            "keepalives" => self.keepalives = Some(try!(parse_int::<u32>(&key, &value)) != 0),
+            "keepalives_idle" => {
+                self.keepalives_idle = Some(try!(parse_duration(&key, &value, 1000)))
+            }
+            "keepalives_interval" => {
+                self.keepalives_interval = Some(try!(parse_duration(&key, &value, 1000)))
+            }
+            "keepalives_count" => {
+                self.keepalives_count = match try!(parse_int(&key, &value)) {
+                    0 => Some(None),
+                    count => Some(Some(count)),
+                }
+            }
+            "tcp_user_timeout" => {
+                self.tcp_user_timeout = Some(try!(parse_duration(&key, &value, 1)))
+            }
+            "sslmode" => self.ssl_mode = Some(try!(SslPolicy::parse(&value))),
+            "sslrootcert" => self.ssl_root_cert = Some(PathBuf::from(value)),
+            "sslcert" => self.ssl_cert = Some(PathBuf::from(value)),
+            "sslkey" => self.ssl_key = Some(PathBuf::from(value)),
+            "sslpassword" => self.ssl_password = Some(value),
+            "sslcrl" => self.ssl_crl = Some(PathBuf::from(value)),
+            "sslnegotiation" => self.ssl_negotiation = Some(try!(SslNegotiation::parse(&value))),
+            "application_name" | "options" => self.set_option(key, value),
+            _ => return Err(format!("unknown connection parameter `{}`", key).into()),
+        }
+        Ok(())
+    }
+
+    /// Sets a runtime parameter to be passed to the backend.
+    pub fn set_option(&mut self, key: String, value: String) {
+        self.options.retain(|&(ref k, _)| *k != key);
+        self.options.push((key, value));
+    }
+
+    /// Fills in parameters which were not explicitly set from the service
+    /// file section `name`.
+    fn apply_service(&mut self, name: &str) -> Result<(), Box<StdError + Sync + Send>> {
+        let mut service = Builder::new();
+        for (key, value) in try!(service::find(name)) {
+            if key == "service" {
+                return Err(format!("nested service specifications are not supported in \
+                                    service `{}`",
+                                   name)
+                               .into());
+            }
+            try!(service.set(key, value));
+        }
+
+        self.host = self.host.take().or(service.host);
+        self.port = self.port.take().or(service.port);
+        self.user = self.user.take().or(service.user);
+        self.password = self.password.take().or(service.password);
+        self.database = self.database.take().or(service.database);
+        self.channel_binding = self.channel_binding.or(service.channel_binding);
+        self.ssl_mode = self.ssl_mode.or(service.ssl_mode);
+        self.ssl_root_cert = self.ssl_root_cert.take().or(service.ssl_root_cert);
+        self.ssl_cert = self.ssl_cert.take().or(service.ssl_cert);
+        self.ssl_key = self.ssl_key.take().or(service.ssl_key);
+        self.ssl_password = self.ssl_password.take().or(service.ssl_password);
+        self.ssl_crl = self.ssl_crl.take().or(service.ssl_crl);
+        self.ssl_negotiation = self.ssl_negotiation.or(service.ssl_negotiation);
+        self.target_session_attrs = self.target_session_attrs.or(service.target_session_attrs);
+        self.load_balance_hosts = self.load_balance_hosts.or(service.load_balance_hosts);
+        self.connect_timeout = self.connect_timeout.or(service.connect_timeout);
+        self.keepalives = self.keepalives.or(service.keepalives);
+        self.keepalives_idle = self.keepalives_idle.or(service.keepalives_idle);
+        self.keepalives_interval = self.keepalives_interval.or(service.keepalives_interval);
+        self.keepalives_count = self.keepalives_count.or(service.keepalives_count);
+        self.tcp_user_timeout = self.tcp_user_timeout.or(service.tcp_user_timeout);
+        for (key, value) in service.options {
+            if !self.options.iter().any(|&(ref k, _)| *k == key) {
+                self.options.push((key, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates a `ConnectParams`, filling in unspecified parameters from the
+    /// service file and environment.
+    pub fn build(mut self) -> Result<ConnectParams, Box<StdError + Sync + Send>> {
+        if let Some(service) = self.service.take().or_else(defaults::service) {
+            try!(self.apply_service(&service));
+        }
+
+        let mut hosts = match self.host {
+            Some(host) => host.into_iter().map(|t| (t, None)).collect(),
+            None => try!(defaults::hosts()),
+        };
+        if let Some(ports) = self.port {
+            let ports = try!(match_ports(ports, hosts.len()));
+            for (host, port) in hosts.iter_mut().zip(ports) {
+                host.1 = port;
+            }
+        }
+        let (target, port, alternate_hosts) = split_hosts(hosts);
+
+        let channel_binding = match self.channel_binding {
+            Some(channel_binding) => channel_binding,
+            None => try!(defaults::channel_binding()),
+        };
+        let ssl_mode = match self.ssl_mode {
+            Some(ssl_mode) => Some(ssl_mode),
+            None => try!(defaults::ssl_mode()),
+        };
+        let ssl_files = SslFiles {
+            root_cert: self.ssl_root_cert.or_else(|| defaults::path("PGSSLROOTCERT")),
+            cert: self.ssl_cert.or_else(|| defaults::path("PGSSLCERT")),
+            key: self.ssl_key.or_else(|| defaults::path("PGSSLKEY")),
+            key_password: self.ssl_password,
+            crl: self.ssl_crl.or_else(|| defaults::path("PGSSLCRL")),
+        };
+        let ssl_negotiation = match self.ssl_negotiation {
+            Some(ssl_negotiation) => ssl_negotiation,
+            None => try!(defaults::ssl_negotiation()),
+        };
+        let target_session_attrs = match self.target_session_attrs {
+            Some(target_session_attrs) => target_session_attrs,
+            None => try!(defaults::target_session_attrs()),
+        };
+        let load_balance_hosts = match self.load_balance_hosts {
+            Some(load_balance_hosts) => load_balance_hosts,
+            None => try!(defaults::load_balance_hosts()),
+        };
+
+        let connect_timeout = match self.connect_timeout {
+            Some(connect_timeout) => connect_timeout,
+            None => try!(defaults::connect_timeout()),
+        };
+
+        let user = match (self.user.or_else(defaults::user), self.password) {
+            (Some(user), password) => {
+                Some(UserInfo {
+                    user: user,
+                    password: password,
+                })
+            }
+            (None, Some(_)) => return Err("a password was specified without a user".into()),
+            (None, None) => None,
+        };
+
+        let mut params = ConnectParams {
+            target: target,
+            port: port,
+            alternate_hosts: alternate_hosts,
+            user: user,
+            database: self.database,
+            options: self.options,
+            channel_binding: channel_binding,
+            ssl_mode: ssl_mode,
+            ssl_files: ssl_files,
+            ssl_negotiation: ssl_negotiation,
+            password_provider: None,
+            target_session_attrs: target_session_attrs,
+            load_balance_hosts: load_balance_hosts,
+            connect_timeout: connect_timeout,
+            keepalives: self.keepalives.unwrap_or(true),
+            keepalives_idle: self.keepalives_idle.unwrap_or(None),
+            keepalives_interval: self.keepalives_interval.unwrap_or(None),
+            keepalives_count: self.keepalives_count.unwrap_or(None),
+            tcp_user_timeout: self.tcp_user_timeout.unwrap_or(None),
+        };
+        try!(defaults::apply(&mut params));
+        Ok(params)
+    }
+}
+
+/// Parses a comma separated list of hosts.
+pub fn parse_hosts(s: &str) -> Result<Vec<ConnectTarget>, Box<StdError + Sync + Send>> {
+    let mut hosts = vec![];
+    for host in s.split(',') {
+        if host.is_empty() {
+            return Err("empty host in host list".into());
+        }
+        hosts.push(try!(make_target(host.to_owned())));
+    }
+    Ok(hosts)
+}
+
+/// Parses a comma separated list of ports. Empty entries use the default
+/// port.
+pub fn parse_ports(s: &str) -> Result<Vec<Option<u16>>, Box<StdError + Sync + Send>> {
+    let mut ports = vec![];
+    for port in s.split(',') {
+        if port.is_empty() {
+            ports.push(None);
+            continue;
+        }
+        match port.parse() {
+            Ok(port) => ports.push(Some(port)),
+            Err(_) => return Err(format!("invalid port `{}`", port).into()),
+        }
+    }
+    Ok(ports)
+}
+
+/// Matches a list of ports to `hosts` hosts.
+///
+/// A single port applies to every host.
+pub fn match_ports(ports: Vec<Option<u16>>,
+                   hosts: usize)
+                   -> Result<Vec<Option<u16>>, Box<StdError + Sync + Send>> {
+    if ports.len() == 1 {
+        Ok(vec![ports[0]; hosts])
+    } else if ports.len() == hosts {
+        Ok(ports)
+    } else {
+        Err(format!("could not match {} port numbers to {} hosts", ports.len(), hosts).into())
+    }
+}
+
+/// Parses a `connect_timeout` value in seconds.
+///
+/// As with libpq, zero or a negative value means no timeout, and timeouts
+/// shorter than two seconds are rounded up.
+pub fn parse_connect_timeout(s: &str) -> Result<Option<Duration>, Box<StdError + Sync + Send>> {
+    match try!(parse_int::<i64>("connect_timeout", s)) {
+        timeout if timeout <= 0 => Ok(None),
+        timeout => Ok(Some(Duration::from_secs(cmp::max(timeout, 2) as u64))),
+    }
+}
+
+// Zero means the operating system's default.
+fn parse_duration(key: &str,
+                  s: &str,
+                  millis_per_unit: u64)
+                  -> Result<Option<Duration>, Box<StdError + Sync + Send>> {
+    match try!(parse_int::<u64>(key, s)) {
+        0 => Ok(None),
+        value => {
+            match value.checked_mul(millis_per_unit) {
+                Some(millis) => Ok(Some(Duration::from_millis(millis))),
+                None => Err(format!("{} value `{}` is out of range", key, s).into()),
+            }
+        }
+    }
+}
+
+fn parse_int<T: FromStr>(key: &str, s: &str) -> Result<T, Box<StdError + Sync + Send>> {
+    s.trim().parse().map_err(|_| format!("invalid {} value `{}`", key, s).into())
+}
+
+/// Converts `params` into keyword/value pairs which `from_pairs` will
+/// convert back into equivalent parameters.
+///
+/// Runtime parameters other than `application_name` and `options` are folded
+/// into `options` as `-c name=value` arguments. The password provider cannot
+/// be represented.
+///
+/// Parameters are written out even when they have their default values so
+/// that the environment cannot change them when the pairs are parsed again.
+/// Unset optional parameters, such as the user, are omitted, and may still be
+/// filled in from the environment or a service file.
+pub fn to_pairs(params: &ConnectParams) -> Vec<(&'static str, String)> {
+    let mut pairs = vec![];
+
+    let hosts = Some((&params.target, params.port))
+                    .into_iter()
+                    .chain(params.alternate_hosts.iter().map(|&(ref t, p)| (t, p)))
+                    .collect::<Vec<_>>();
+    let targets = hosts.iter().map(|&(target, _)| target_str(target)).collect::<Vec<_>>();
+    pairs.push(("host", targets.join(",")));
+    if hosts.iter().any(|&(_, port)| port.is_some()) {
+        let ports = hosts.iter()
+                         .map(|&(_, port)| port.map_or(String::new(), |p| p.to_string()))
+                         .collect::<Vec<_>>();
+        pairs.push(("port", ports.join(",")));
+    }
+
+    if let Some(ref user) = params.user {
+        pairs.push(("user", user.user.clone()));
+        if let Some(ref password) = user.password {
+            pairs.push(("password", password.clone()));
+        }
+    }
+    if let Some(ref database) = params.database {
+        pairs.push(("dbname", database.clone()));
+    }
+
+    let mut options = vec![];
+    for &(ref key, ref value) in &params.options {
+        match &**key {
+            "application_name" => pairs.push(("application_name", value.clone())),
+            "options" => options.push(value.clone()),
+            _ => options.push(format!("-c {}={}", key, escape_option(value))),
+        }
+    }
+    if !options.is_empty() {
+        pairs.push(("options", options.join(" ")));
+    }
+
+    pairs.push(("channel_binding", params.channel_binding.as_str().to_owned()));
+    if let Some(ssl_mode) = params.ssl_mode {
+        pairs.push(("sslmode", ssl_mode.as_str().to_owned()));
+    }
+    let files = &params.ssl_files;
+    for &(key, path) in &[("sslrootcert", &files.root_cert),
+                          ("sslcert", &files.cert),
+                          ("sslkey", &files.key),
+                          ("sslcrl", &files.crl)] {
+        if let Some(ref path) = *path {
+            pairs.push((key, path.to_string_lossy().into_owned()));
+        }
+    }
+    if let Some(ref password) = files.key_password {
+        pairs.push(("sslpassword", password.clone()));
+    }
+    pairs.push(("sslnegotiation", params.ssl_negotiation.as_str().to_owned()));
+    pairs.push(("target_session_attrs", params.target_session_attrs.as_str().to_owned()));
+    pairs.push(("load_balance_hosts", params.load_balance_hosts.as_str().to_owned()));
+
+    if let Some(timeout) = params.connect_timeout {
+        pairs.push(("connect_timeout", round_up(timeout, 1000).to_string()));
+    }
+    pairs.push(("keepalives", if params.keepalives { "1" } else { "0" }.to_owned()));
+    if let Some(idle) = params.keepalives_idle {
+        pairs.push(("keepalives_idle", round_up(idle, 1000).to_string()));
+    }
+    if let Some(interval) = params.keepalives_interval {
+        pairs.push(("keepalives_interval", round_up(interval, 1000).to_string()));
+    }
+    if let Some(count) = params.keepalives_count {
+        pairs.push(("keepalives_count", count.to_string()));
+    }
+    if let Some(timeout) = params.tcp_user_timeout {
+        pairs.push(("tcp_user_timeout", round_up(timeout, 1).to_string()));
+    }
+
+    pairs
+}
+
+fn target_str(target: &ConnectTarget) -> String {
+    match *target {
+        ConnectTarget::Tcp(ref host) => host.clone(),
+        #[cfg(feature = "unix_socket")]
+        ConnectTarget::Unix(ref path) => path.to_string_lossy().into_owned(),
+    }
+}
+
+// Postgres splits the options parameter on whitespace, which can be escaped
+// with a backslash.
+fn escape_option(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_whitespace() || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// `millis_per_unit` must divide 1000. Durations too long to be represented
+// saturate rather than overflowing.
+pub fn round_up(duration: Duration, millis_per_unit: u64) -> u64 {
+    let nanos_per_unit = millis_per_unit * 1000000;
+    let subsec_units = (duration.subsec_nanos() as u64 + nanos_per_unit - 1) / nanos_per_unit;
+    duration.as_secs()
+            .checked_mul(1000 / millis_per_unit)
+            .and_then(|units| units.checked_add(subsec_units))
+            .unwrap_or(u64::max_value())
+}
+
+/// Formats keyword/value pairs as a connection string.
+pub fn format(pairs: &[(&str, String)]) -> String {
+    let mut s = String::new();
+    for &(key, ref value) in pairs {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        s.push_str(key);
+        s.push('=');
+
+        let quote = value.is_empty() ||
+                    value.chars().any(|c| c.is_whitespace() || c == '\'' || c == '\\');
+        if !quote {
+            s.push_str(value);
+            continue;
+        }
+
+        s.push('\'');
+        for c in value.chars() {
+            if c == '\'' || c == '\\' {
+                s.push('\\');
+            }
+            s.push(c);
+        }
+        s.push('\'');
+    }
+    s
+}
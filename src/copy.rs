@@ -0,0 +1,787 @@
+//! Encoding and decoding of `COPY` data.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::mem;
+use std::ops::Range;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use {Result, WrongTypeNew};
+use error::Error;
+use stmt::{CopyInfo, ReadWithInfo, WriteWithInfo};
+use types::{FromSql, IsNull, SessionInfo, ToSql, Type, WrongType};
+
+const BINARY_SIGNATURE: &'static [u8] = b"PGCOPY\n\xff\r\n\0";
+const HAS_OIDS: i32 = 1 << 16;
+
+enum WriteState {
+    Header,
+    Body,
+    Done,
+}
+
+/// Encodes rows into the binary `COPY` format.
+///
+/// The values of each row are converted with `ToSql` in the same way as
+/// query parameters. A `BinaryCopyWriter` is passed to `Statement::copy_in`
+/// as the source of the data, which must be a `COPY ... FROM STDIN (FORMAT
+/// binary)` statement whose columns have the types given to the writer.
+///
+/// If a row has the wrong number of values or a value cannot be converted,
+/// the `COPY` is aborted and `copy_in` returns the error.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// use postgres::copy::BinaryCopyWriter;
+/// use postgres::types::{ToSql, Type};
+///
+/// # let conn = Connection::connect("", SslMode::None).unwrap();
+/// let rows: Vec<[&ToSql; 2]> = vec![[&1i32, &"john"], [&2i32, &None::<String>]];
+/// let types = [Type::Int4, Type::Varchar];
+/// let mut writer = BinaryCopyWriter::new(&types, rows.iter());
+///
+/// let stmt = conn.prepare("COPY people (id, name) FROM STDIN (FORMAT binary)").unwrap();
+/// stmt.copy_in(&[], &mut writer).unwrap();
+/// ```
+pub struct BinaryCopyWriter<'a, I> {
+    types: &'a [Type],
+    rows: I,
+    state: WriteState,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<'a, 'b, I> BinaryCopyWriter<'a, I>
+    where I: Iterator,
+          I::Item: AsRef<[&'b ToSql]>
+{
+    /// Creates a new `BinaryCopyWriter` which encodes the rows produced by
+    /// an iterator.
+    ///
+    /// Each row must contain one value for each of the types.
+    pub fn new(types: &'a [Type], rows: I) -> BinaryCopyWriter<'a, I> {
+        BinaryCopyWriter {
+            types: types,
+            rows: rows,
+            state: WriteState::Header,
+            buf: vec![],
+            pos: 0,
+        }
+    }
+
+    fn fill_buf(&mut self, info: &CopyInfo) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+
+        match self.state {
+            WriteState::Header => {
+                try!(self.buf.write_all(BINARY_SIGNATURE));
+                // flags
+                try!(self.buf.write_i32::<BigEndian>(0));
+                // header extension length
+                try!(self.buf.write_i32::<BigEndian>(0));
+                self.state = WriteState::Body;
+            }
+            WriteState::Body => {
+                match self.rows.next() {
+                    Some(row) => try!(self.write_row(row.as_ref(), info)),
+                    None => {
+                        try!(self.buf.write_i16::<BigEndian>(-1));
+                        self.state = WriteState::Done;
+                    }
+                }
+            }
+            WriteState::Done => {}
+        }
+
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[&ToSql], info: &CopyInfo) -> io::Result<()> {
+        if row.len() != self.types.len() {
+            let msg = format!("expected {} values but got {}", self.types.len(), row.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        let session_info = info.session_info();
+        try!(self.buf.write_i16::<BigEndian>(self.types.len() as i16));
+
+        let mut value = vec![];
+        for (v, ty) in row.iter().zip(self.types) {
+            value.clear();
+            match try!(v.to_sql_checked(ty, &mut value, &session_info)) {
+                IsNull::Yes => try!(self.buf.write_i32::<BigEndian>(-1)),
+                IsNull::No => {
+                    if value.len() > i32::max_value() as usize {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                                  "value too large to transmit"));
+                    }
+                    try!(self.buf.write_i32::<BigEndian>(value.len() as i32));
+                    try!(self.buf.write_all(&value));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, 'b, I> ReadWithInfo for BinaryCopyWriter<'a, I>
+    where I: Iterator,
+          I::Item: AsRef<[&'b ToSql]>
+{
+    fn read_with_info(&mut self, buf: &mut [u8], info: &CopyInfo) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if let WriteState::Done = self.state {
+                return Ok(0);
+            }
+            try!(self.fill_buf(info));
+        }
+
+        let len = try!((&self.buf[self.pos..]).read(buf));
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+enum ReadState {
+    Header,
+    Body,
+    Done,
+}
+
+/// Decodes rows from the binary `COPY` format.
+///
+/// A `BinaryCopyReader` is passed to `Statement::copy_out` as the
+/// destination of the data, which must be produced by a `COPY ... TO STDOUT
+/// (FORMAT binary)` statement whose columns have the types given to the
+/// reader. The provided function is called with each row as it is received,
+/// and its values are converted with `FromSql` in the same way as query
+/// results.
+///
+/// If the data is malformed or the function returns an error, `copy_out`
+/// returns the error and the remaining data is discarded.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// use postgres::copy::BinaryCopyReader;
+/// use postgres::types::Type;
+///
+/// # let conn = Connection::connect("", SslMode::None).unwrap();
+/// let mut people = vec![];
+/// {
+///     let types = [Type::Int4, Type::Varchar];
+///     let mut reader = BinaryCopyReader::new(&types, |row| {
+///         people.push((row.get::<i32>(0), row.get::<Option<String>>(1)));
+///         Ok(())
+///     });
+///
+///     let stmt = conn.prepare("COPY people (id, name) TO STDOUT (FORMAT binary)").unwrap();
+///     stmt.copy_out(&[], &mut reader).unwrap();
+///     reader.finish().unwrap();
+/// }
+/// ```
+pub struct BinaryCopyReader<'a, F> {
+    types: &'a [Type],
+    f: F,
+    state: ReadState,
+    has_oids: bool,
+    buf: Vec<u8>,
+    ranges: Vec<Option<Range<usize>>>,
+}
+
+impl<'a, F> BinaryCopyReader<'a, F>
+    where F: FnMut(BinaryCopyRow) -> Result<()>
+{
+    /// Creates a new `BinaryCopyReader` which passes each decoded row to a
+    /// function.
+    ///
+    /// Each row must contain one value for each of the types.
+    pub fn new(types: &'a [Type], f: F) -> BinaryCopyReader<'a, F> {
+        BinaryCopyReader {
+            types: types,
+            f: f,
+            state: ReadState::Header,
+            has_oids: false,
+            buf: vec![],
+            ranges: vec![],
+        }
+    }
+
+    /// Consumes the reader, returning an error if the data ended before the
+    /// binary `COPY` trailer.
+    ///
+    /// `copy_out` does not check this itself, so it should be called once
+    /// the `COPY` has completed to detect truncated data.
+    pub fn finish(self) -> Result<()> {
+        match self.state {
+            ReadState::Done => Ok(()),
+            _ => Err(Error::Io(invalid_data("binary COPY data ended before its trailer"))),
+        }
+    }
+
+    // Returns the number of bytes consumed, or 0 if more data is needed.
+    fn parse(&mut self, info: &CopyInfo) -> io::Result<usize> {
+        match self.state {
+            ReadState::Header => self.parse_header(),
+            ReadState::Body => self.parse_row(info),
+            ReadState::Done => Err(invalid_data("unexpected data after the COPY trailer")),
+        }
+    }
+
+    fn parse_header(&mut self) -> io::Result<usize> {
+        let len = BINARY_SIGNATURE.len() + 8;
+        if self.buf.len() < len {
+            return Ok(0);
+        }
+
+        if &self.buf[..BINARY_SIGNATURE.len()] != BINARY_SIGNATURE {
+            return Err(invalid_data("invalid binary COPY signature"));
+        }
+        let mut header = &self.buf[BINARY_SIGNATURE.len()..];
+        let flags = try!(header.read_i32::<BigEndian>());
+        if flags & !HAS_OIDS != 0 {
+            return Err(invalid_data("unsupported binary COPY flags"));
+        }
+        let extension_len = try!(header.read_i32::<BigEndian>());
+        if extension_len < 0 {
+            return Err(invalid_data("invalid binary COPY header extension length"));
+        }
+
+        let len = len + extension_len as usize;
+        if self.buf.len() < len {
+            return Ok(0);
+        }
+        self.has_oids = flags & HAS_OIDS != 0;
+        self.state = ReadState::Body;
+        Ok(len)
+    }
+
+    fn parse_row(&mut self, info: &CopyInfo) -> io::Result<usize> {
+        if self.buf.len() < 2 {
+            return Ok(0);
+        }
+        let count = try!((&self.buf[..]).read_i16::<BigEndian>());
+        if count == -1 {
+            self.state = ReadState::Done;
+            return Ok(2);
+        }
+        if count as usize != self.types.len() {
+            let msg = format!("expected {} values but got {}", self.types.len(), count);
+            return Err(invalid_data(&msg));
+        }
+
+        let mut pos = 2;
+        self.ranges.clear();
+        let fields = if self.has_oids { count as usize + 1 } else { count as usize };
+        for _ in 0..fields {
+            if self.buf.len() < pos + 4 {
+                return Ok(0);
+            }
+            let len = try!((&self.buf[pos..]).read_i32::<BigEndian>());
+            pos += 4;
+            if len == -1 {
+                self.ranges.push(None);
+            } else if len < 0 {
+                return Err(invalid_data("invalid binary COPY field length"));
+            } else {
+                let end = pos + len as usize;
+                if self.buf.len() < end {
+                    return Ok(0);
+                }
+                self.ranges.push(Some(pos..end));
+                pos = end;
+            }
+        }
+
+        if self.has_oids {
+            self.ranges.remove(0);
+        }
+        let row = BinaryCopyRow {
+            types: self.types,
+            buf: &self.buf,
+            ranges: &self.ranges,
+            info: info.session_info(),
+        };
+        try!((self.f)(row));
+        Ok(pos)
+    }
+}
+
+impl<'a, F> WriteWithInfo for BinaryCopyReader<'a, F>
+    where F: FnMut(BinaryCopyRow) -> Result<()>
+{
+    fn write_with_info(&mut self, buf: &[u8], info: &CopyInfo) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        while !self.buf.is_empty() {
+            let len = try!(self.parse(info));
+            if len == 0 {
+                break;
+            }
+            self.buf.drain(..len);
+        }
+        Ok(buf.len())
+    }
+}
+
+/// A row of data decoded by a `BinaryCopyReader`.
+pub struct BinaryCopyRow<'a> {
+    types: &'a [Type],
+    buf: &'a [u8],
+    ranges: &'a [Option<Range<usize>>],
+    info: SessionInfo<'a>,
+}
+
+impl<'a> fmt::Debug for BinaryCopyRow<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BinaryCopyRow")
+           .field("types", &self.types)
+           .finish()
+    }
+}
+
+impl<'a> BinaryCopyRow<'a> {
+    /// Returns the number of values in the row.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Returns the types of the values in the row.
+    pub fn types(&self) -> &[Type] {
+        self.types
+    }
+
+    /// Retrieves the contents of a field of the row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds or the return type is not
+    /// compatible with the Postgres type.
+    pub fn get<T>(&self, idx: usize) -> T
+        where T: FromSql
+    {
+        match self.get_opt(idx) {
+            Some(Ok(ok)) => ok,
+            Some(Err(err)) => panic!("error retrieving column {}: {:?}", idx, err),
+            None => panic!("no such column {}", idx),
+        }
+    }
+
+    /// Retrieves the contents of a field of the row.
+    ///
+    /// Returns `None` if the index is out of bounds, `Some(Err(..))` if there
+    /// was an error converting the value, and `Some(Ok(..))` on success.
+    pub fn get_opt<T>(&self, idx: usize) -> Option<Result<T>>
+        where T: FromSql
+    {
+        let ty = match self.types.get(idx) {
+            Some(ty) => ty,
+            None => return None,
+        };
+
+        if !<T as FromSql>::accepts(ty) {
+            return Some(Err(Error::Conversion(Box::new(WrongType::new(ty.clone())))));
+        }
+        let value = match self.ranges[idx] {
+            Some(ref range) => FromSql::from_sql(ty, &mut &self.buf[range.clone()], &self.info),
+            None => FromSql::from_sql_null(ty, &self.info),
+        };
+        Some(value)
+    }
+
+    /// Retrieves the specified field as a raw buffer of Postgres data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn get_bytes(&self, idx: usize) -> Option<&[u8]> {
+        self.ranges[idx].as_ref().map(|range| &self.buf[range.clone()])
+    }
+}
+
+/// The text `COPY` format.
+///
+/// Values are provided and returned in their Postgres text representation,
+/// with `None` representing `NULL`. Delimiters, newlines and backslashes
+/// within values are escaped with backslashes.
+///
+/// Rows can be written to a `CopyInWriter` and read from a `CopyOutReader`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// use postgres::copy::TextFormat;
+///
+/// # let conn = Connection::connect("", SslMode::None).unwrap();
+/// let format = TextFormat::new();
+///
+/// let stmt = conn.prepare("COPY people (id, name) FROM STDIN").unwrap();
+/// let mut writer = stmt.copy_in_writer(&[]).unwrap();
+/// format.write_row(&mut writer, &[Some("1"), Some("john\tdoe")]).unwrap();
+/// format.write_row(&mut writer, &[Some("2"), None]).unwrap();
+/// writer.finish().unwrap();
+///
+/// let stmt = conn.prepare("COPY people (id, name) TO STDOUT").unwrap();
+/// let mut reader = stmt.copy_out_reader(&[]).unwrap();
+/// while let Some(row) = format.read_row(&mut reader).unwrap() {
+///     println!("{:?}", row);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextFormat {
+    delimiter: u8,
+    null: String,
+}
+
+impl Default for TextFormat {
+    fn default() -> TextFormat {
+        TextFormat::new()
+    }
+}
+
+impl TextFormat {
+    /// Creates a new `TextFormat` with the default options of Postgres.
+    ///
+    /// The delimiter is a tab and the null string is `\N`.
+    pub fn new() -> TextFormat {
+        TextFormat {
+            delimiter: b'\t',
+            null: "\\N".to_owned(),
+        }
+    }
+
+    /// Sets the character which separates the values of a row.
+    ///
+    /// This corresponds to the `DELIMITER` option of `COPY`.
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut TextFormat {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the string which represents a `NULL` value.
+    ///
+    /// This corresponds to the `NULL` option of `COPY`.
+    pub fn null(&mut self, null: &str) -> &mut TextFormat {
+        self.null = null.to_owned();
+        self
+    }
+
+    /// Writes a row of values.
+    ///
+    /// An error is returned if a value is indistinguishable from the null
+    /// string.
+    pub fn write_row<W: Write>(&self, w: &mut W, row: &[Option<&str>]) -> io::Result<()> {
+        let mut buf = vec![];
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                buf.push(self.delimiter);
+            }
+
+            let value = match *value {
+                Some(value) => value,
+                None => {
+                    buf.extend_from_slice(self.null.as_bytes());
+                    continue;
+                }
+            };
+
+            let start = buf.len();
+            for &b in value.as_bytes() {
+                match b {
+                    b'\\' => buf.extend_from_slice(b"\\\\"),
+                    b'\n' => buf.extend_from_slice(b"\\n"),
+                    b'\r' => buf.extend_from_slice(b"\\r"),
+                    b'\t' => buf.extend_from_slice(b"\\t"),
+                    b if b == self.delimiter => buf.extend_from_slice(&[b'\\', b]),
+                    b => buf.push(b),
+                }
+            }
+            if &buf[start..] == self.null.as_bytes() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "value is indistinguishable from the null string"));
+            }
+        }
+        buf.push(b'\n');
+        w.write_all(&buf)
+    }
+
+    /// Reads a row of values, returning `None` at the end of the data.
+    pub fn read_row<R: BufRead>(&self, r: &mut R) -> io::Result<Option<Vec<Option<String>>>> {
+        let mut line = vec![];
+        if try!(r.read_until(b'\n', &mut line)) == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+
+        let mut row = vec![];
+        let mut start = 0;
+        let mut i = 0;
+        while i < line.len() {
+            if line[i] == b'\\' {
+                i += 2;
+            } else if line[i] == self.delimiter {
+                row.push(try!(self.read_value(&line[start..i])));
+                i += 1;
+                start = i;
+            } else {
+                i += 1;
+            }
+        }
+        row.push(try!(self.read_value(&line[start..])));
+        Ok(Some(row))
+    }
+
+    fn read_value(&self, raw: &[u8]) -> io::Result<Option<String>> {
+        if raw == self.null.as_bytes() {
+            return Ok(None);
+        }
+
+        let mut value = vec![];
+        let mut it = raw.iter().cloned().peekable();
+        while let Some(b) = it.next() {
+            if b != b'\\' {
+                value.push(b);
+                continue;
+            }
+
+            match it.next() {
+                Some(b'b') => value.push(8),
+                Some(b'f') => value.push(12),
+                Some(b'n') => value.push(b'\n'),
+                Some(b'r') => value.push(b'\r'),
+                Some(b't') => value.push(b'\t'),
+                Some(b'v') => value.push(11),
+                Some(b'x') if it.peek().and_then(|&b| digit(b, 16)).is_some() => {
+                    let mut n = 0;
+                    for _ in 0..2 {
+                        match it.peek().and_then(|&b| digit(b, 16)) {
+                            Some(d) => n = n * 16 + d,
+                            None => break,
+                        }
+                        it.next();
+                    }
+                    value.push(n);
+                }
+                Some(b) if digit(b, 8).is_some() => {
+                    let mut n = digit(b, 8).unwrap();
+                    for _ in 0..2 {
+                        match it.peek().and_then(|&b| digit(b, 8)) {
+                            Some(d) => n = n.wrapping_mul(8).wrapping_add(d),
+                            None => break,
+                        }
+                        it.next();
+                    }
+                    value.push(n);
+                }
+                Some(b) => value.push(b),
+                None => value.push(b'\\'),
+            }
+        }
+
+        String::from_utf8(value).map(Some).map_err(|err| invalid_data(&err.to_string()))
+    }
+}
+
+/// The CSV `COPY` format.
+///
+/// Values are provided and returned in their Postgres text representation,
+/// with `None` representing `NULL`. Values containing delimiters, quotes,
+/// escapes or newlines, or which match the null string, are quoted.
+///
+/// Rows can be written to a `CopyInWriter` and read from a `CopyOutReader`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// use postgres::copy::CsvFormat;
+///
+/// # let conn = Connection::connect("", SslMode::None).unwrap();
+/// let mut format = CsvFormat::new();
+/// format.delimiter(b';').null("NULL");
+///
+/// let stmt = conn.prepare("COPY people (id, name) FROM STDIN \
+///                          (FORMAT csv, DELIMITER ';', NULL 'NULL')").unwrap();
+/// let mut writer = stmt.copy_in_writer(&[]).unwrap();
+/// format.write_row(&mut writer, &[Some("1"), Some("doe; john")]).unwrap();
+/// format.write_row(&mut writer, &[Some("2"), None]).unwrap();
+/// writer.finish().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CsvFormat {
+    delimiter: u8,
+    quote: u8,
+    escape: u8,
+    null: String,
+}
+
+impl Default for CsvFormat {
+    fn default() -> CsvFormat {
+        CsvFormat::new()
+    }
+}
+
+impl CsvFormat {
+    /// Creates a new `CsvFormat` with the default options of Postgres.
+    ///
+    /// The delimiter is a comma, the quote and escape characters are double
+    /// quotes and the null string is empty.
+    pub fn new() -> CsvFormat {
+        CsvFormat {
+            delimiter: b',',
+            quote: b'"',
+            escape: b'"',
+            null: String::new(),
+        }
+    }
+
+    /// Sets the character which separates the values of a row.
+    ///
+    /// This corresponds to the `DELIMITER` option of `COPY`.
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut CsvFormat {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the character which quotes values.
+    ///
+    /// This corresponds to the `QUOTE` option of `COPY`.
+    pub fn quote(&mut self, quote: u8) -> &mut CsvFormat {
+        self.quote = quote;
+        self
+    }
+
+    /// Sets the character which escapes quote characters within quoted
+    /// values.
+    ///
+    /// This corresponds to the `ESCAPE` option of `COPY`.
+    pub fn escape(&mut self, escape: u8) -> &mut CsvFormat {
+        self.escape = escape;
+        self
+    }
+
+    /// Sets the string which represents a `NULL` value.
+    ///
+    /// This corresponds to the `NULL` option of `COPY`.
+    pub fn null(&mut self, null: &str) -> &mut CsvFormat {
+        self.null = null.to_owned();
+        self
+    }
+
+    /// Writes a row of values.
+    pub fn write_row<W: Write>(&self, w: &mut W, row: &[Option<&str>]) -> io::Result<()> {
+        let mut buf = vec![];
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                buf.push(self.delimiter);
+            }
+
+            let value = match *value {
+                Some(value) => value,
+                None => {
+                    buf.extend_from_slice(self.null.as_bytes());
+                    continue;
+                }
+            };
+
+            let needs_quotes = value == self.null || value == "\\." ||
+                               value.bytes().any(|b| self.is_special(b));
+            if !needs_quotes {
+                buf.extend_from_slice(value.as_bytes());
+                continue;
+            }
+
+            buf.push(self.quote);
+            for b in value.bytes() {
+                if b == self.quote || b == self.escape {
+                    buf.push(self.escape);
+                }
+                buf.push(b);
+            }
+            buf.push(self.quote);
+        }
+        buf.push(b'\n');
+        w.write_all(&buf)
+    }
+
+    /// Reads a row of values, returning `None` at the end of the data.
+    pub fn read_row<R: BufRead>(&self, r: &mut R) -> io::Result<Option<Vec<Option<String>>>> {
+        let mut line = vec![];
+        if try!(r.read_until(b'\n', &mut line)) == 0 {
+            return Ok(None);
+        }
+
+        let mut row = vec![];
+        let mut value = vec![];
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut i = 0;
+        loop {
+            if i == line.len() {
+                if !in_quotes {
+                    break;
+                }
+                // quoted values can contain newlines
+                if try!(r.read_until(b'\n', &mut line)) == 0 {
+                    return Err(invalid_data("unterminated quoted CSV value"));
+                }
+                continue;
+            }
+
+            let b = line[i];
+            i += 1;
+            if in_quotes {
+                if b == self.escape && i < line.len() &&
+                   (line[i] == self.quote || line[i] == self.escape) {
+                    value.push(line[i]);
+                    i += 1;
+                } else if b == self.quote {
+                    in_quotes = false;
+                } else {
+                    value.push(b);
+                }
+            } else if b == self.quote {
+                in_quotes = true;
+                quoted = true;
+            } else if b == self.delimiter {
+                row.push(try!(self.read_value(&mut value, quoted)));
+                quoted = false;
+            } else if b == b'\n' {
+                break;
+            } else {
+                value.push(b);
+            }
+        }
+
+        row.push(try!(self.read_value(&mut value, quoted)));
+        Ok(Some(row))
+    }
+
+    fn is_special(&self, b: u8) -> bool {
+        b == self.delimiter || b == self.quote || b == self.escape || b == b'\n' || b == b'\r'
+    }
+
+    fn read_value(&self, value: &mut Vec<u8>, quoted: bool) -> io::Result<Option<String>> {
+        let value = mem::replace(value, vec![]);
+        if !quoted && value == self.null.as_bytes() {
+            return Ok(None);
+        }
+
+        String::from_utf8(value).map(Some).map_err(|err| invalid_data(&err.to_string()))
+    }
+}
+
+fn digit(b: u8, radix: u32) -> Option<u8> {
+    (b as char).to_digit(radix).map(|d| d as u8)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,200 @@
+//! Connection parameter defaults taken from the environment.
+//!
+//! See the [Postgres documentation](http://www.postgresql.org/docs/current/static/libpq-envars.html)
+//! for details on the variables.
+use std::env;
+use std::error::Error as StdError;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use {ConnectParams, ConnectTarget, UserInfo, ChannelBinding, TargetSessionAttrs,
+     LoadBalanceHosts, SslPolicy, SslNegotiation};
+use conninfo;
+
+/// Returns the hosts specified by `PGHOST`, or `localhost`.
+///
+/// The ports are not specified. `apply` will set them from `PGPORT`.
+pub fn hosts() -> Result<Vec<(ConnectTarget, Option<u16>)>, Box<StdError + Sync + Send>> {
+    match var("PGHOST") {
+        Some(hosts) => {
+            let hosts = try!(conninfo::parse_hosts(&hosts));
+            Ok(hosts.into_iter().map(|host| (host, None)).collect())
+        }
+        None => Ok(vec![(ConnectTarget::Tcp("localhost".to_owned()), None)]),
+    }
+}
+
+/// Returns the channel binding mode specified by `PGCHANNELBINDING`, or
+/// `ChannelBinding::Prefer`.
+pub fn channel_binding() -> Result<ChannelBinding, Box<StdError + Sync + Send>> {
+    match var("PGCHANNELBINDING") {
+        Some(channel_binding) => ChannelBinding::parse(&channel_binding),
+        None => Ok(ChannelBinding::Prefer),
+    }
+}
+
+/// Returns the SSL mode specified by `PGSSLMODE`.
+pub fn ssl_mode() -> Result<Option<SslPolicy>, Box<StdError + Sync + Send>> {
+    match var("PGSSLMODE") {
+        Some(ssl_mode) => SslPolicy::parse(&ssl_mode).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Returns the SSL negotiation specified by `PGSSLNEGOTIATION`, or
+/// `SslNegotiation::Postgres`.
+pub fn ssl_negotiation() -> Result<SslNegotiation, Box<StdError + Sync + Send>> {
+    match var("PGSSLNEGOTIATION") {
+        Some(ssl_negotiation) => SslNegotiation::parse(&ssl_negotiation),
+        None => Ok(SslNegotiation::Postgres),
+    }
+}
+
+/// Returns the path specified by the environment variable `name`.
+pub fn path(name: &str) -> Option<PathBuf> {
+    var(name).map(PathBuf::from)
+}
+
+/// Returns the server requirement specified by `PGTARGETSESSIONATTRS`, or
+/// `TargetSessionAttrs::Any`.
+pub fn target_session_attrs() -> Result<TargetSessionAttrs, Box<StdError + Sync + Send>> {
+    match var("PGTARGETSESSIONATTRS") {
+        Some(target_session_attrs) => TargetSessionAttrs::parse(&target_session_attrs),
+        None => Ok(TargetSessionAttrs::Any),
+    }
+}
+
+/// Returns the host ordering specified by `PGLOADBALANCEHOSTS`, or
+/// `LoadBalanceHosts::Disable`.
+pub fn load_balance_hosts() -> Result<LoadBalanceHosts, Box<StdError + Sync + Send>> {
+    match var("PGLOADBALANCEHOSTS") {
+        Some(load_balance_hosts) => LoadBalanceHosts::parse(&load_balance_hosts),
+        None => Ok(LoadBalanceHosts::Disable),
+    }
+}
+
+/// Returns the connection timeout specified by `PGCONNECT_TIMEOUT`.
+pub fn connect_timeout() -> Result<Option<Duration>, Box<StdError + Sync + Send>> {
+    match var("PGCONNECT_TIMEOUT") {
+        Some(connect_timeout) => conninfo::parse_connect_timeout(&connect_timeout),
+        None => Ok(None),
+    }
+}
+
+/// Returns the user specified by `PGUSER`, or the name of the user running
+/// the process.
+///
+/// On Unix the name is looked up in the password database, falling back to
+/// `USER` and `LOGNAME`. On Windows it is taken from `USERNAME`.
+pub fn user() -> Option<String> {
+    var("PGUSER").or_else(os_user)
+}
+
+#[cfg(unix)]
+fn os_user() -> Option<String> {
+    passwd_user().or_else(|| var("USER")).or_else(|| var("LOGNAME"))
+}
+
+// Like libpq, use the password database entry of the effective user, since
+// `USER` and `LOGNAME` are often unset in containers and services.
+#[cfg(unix)]
+fn passwd_user() -> Option<String> {
+    use std::ffi::CStr;
+    use std::mem;
+    use std::ptr;
+    use libc;
+
+    let mut buf = vec![0; 1024];
+    loop {
+        let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut result = ptr::null_mut();
+        let ret = unsafe {
+            libc::getpwuid_r(libc::geteuid(),
+                             &mut passwd,
+                             buf.as_mut_ptr(),
+                             buf.len(),
+                             &mut result)
+        };
+        if ret == libc::ERANGE && buf.len() < 1 << 20 {
+            let len = buf.len() * 2;
+            buf.resize(len, 0);
+            continue;
+        }
+        if ret != 0 || result.is_null() || passwd.pw_name.is_null() {
+            return None;
+        }
+
+        let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+        return match name.to_str() {
+            Ok(name) if !name.is_empty() => Some(name.to_owned()),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(windows)]
+fn os_user() -> Option<String> {
+    var("USERNAME")
+}
+
+/// Returns the service specified by `PGSERVICE`.
+pub fn service() -> Option<String> {
+    var("PGSERVICE")
+}
+
+/// Returns the password specified by `PGPASSWORD`.
+pub fn password() -> Option<String> {
+    var("PGPASSWORD")
+}
+
+/// Fills in unspecified parameters from the environment.
+///
+/// The hosts and other non-optional parameters should be defaulted by the
+/// caller while parsing via the other functions in this module.
+pub fn apply(params: &mut ConnectParams) -> Result<(), Box<StdError + Sync + Send>> {
+    if let Some(ports) = var("PGPORT") {
+        let ports = try!(conninfo::parse_ports(&ports));
+        let ports = try!(conninfo::match_ports(ports, 1 + params.alternate_hosts.len()));
+        if params.port.is_none() {
+            params.port = ports[0];
+        }
+        for (host, &port) in params.alternate_hosts.iter_mut().zip(&ports[1..]) {
+            if host.1.is_none() {
+                host.1 = port;
+            }
+        }
+    }
+
+    if params.user.is_none() {
+        params.user = user().map(|user| {
+            UserInfo {
+                user: user,
+                password: None,
+            }
+        });
+    }
+
+    if params.database.is_none() {
+        params.database = var("PGDATABASE");
+    }
+
+    for &(var_name, option) in &[("PGAPPNAME", "application_name"), ("PGOPTIONS", "options")] {
+        if params.options.iter().any(|&(ref k, _)| k == option) {
+            continue;
+        }
+        if let Some(value) = var(var_name) {
+            params.options.push((option.to_owned(), value));
+        }
+    }
+
+    Ok(())
+}
+
+// Like libpq, treat empty variables as unset.
+fn var(name: &str) -> Option<String> {
+    match env::var(name) {
+        Ok(ref value) if value.is_empty() => None,
+        Ok(value) => Some(value),
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,89 @@
+//! Just enough PEM and DER parsing to find certificates' signature
+//! algorithms.
+#[cfg(feature = "openssl")]
+use util::base64_decode;
+
+macro_rules! try_opt {
+    ($e:expr) => (
+        match $e {
+            Some(v) => v,
+            None => return None,
+        }
+    )
+}
+
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+
+/// Returns the contents of the object identifier of the signature algorithm
+/// of the DER encoded certificate `cert`.
+pub fn signature_algorithm(cert: &[u8]) -> Option<&[u8]> {
+    let (cert, _) = try_opt!(expect(cert, SEQUENCE));
+    let (_, rest) = try_opt!(expect(cert, SEQUENCE));
+    let (algorithm, _) = try_opt!(expect(rest, SEQUENCE));
+    let (oid, _) = try_opt!(expect(algorithm, OBJECT_IDENTIFIER));
+    Some(oid)
+}
+
+/// Extracts the DER encoded contents of the PEM blocks labeled `label`.
+#[cfg(feature = "openssl")]
+pub fn pem_blocks(pem: &str, label: &str) -> Result<Vec<Vec<u8>>, String> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let mut blocks = vec![];
+    let mut block = None;
+    for line in pem.lines().map(str::trim) {
+        if line == begin {
+            block = Some(String::new());
+        } else if line == end {
+            match block.take() {
+                Some(block) => {
+                    let der = try!(base64_decode(&block).map_err(|e| e.to_string()));
+                    blocks.push(der);
+                }
+                None => return Err(format!("unexpected `{}`", line)),
+            }
+        } else if let Some(ref mut block) = block {
+            block.push_str(line);
+        }
+    }
+
+    if block.is_some() {
+        return Err(format!("missing `{}`", end));
+    }
+    Ok(blocks)
+}
+
+// Returns the contents of the first element of `data` and the data following
+// it if the element has the tag `tag`.
+fn expect(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match next(data) {
+        Some((t, contents, rest)) if t == tag => Some((contents, rest)),
+        _ => None,
+    }
+}
+
+// Splits the first element off of `data`, returning its tag, its contents,
+// and the data following it.
+fn next(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    if data.len() < 2 {
+        return None;
+    }
+
+    let (len, header) = if data[1] < 0x80 {
+        (data[1] as usize, 2)
+    } else {
+        let count = (data[1] & 0x7f) as usize;
+        if count == 0 || count > 4 || data.len() < 2 + count {
+            return None;
+        }
+        let len = data[2..2 + count].iter().fold(0, |len, &b| len << 8 | b as usize);
+        (len, 2 + count)
+    };
+
+    if data.len() - header < len {
+        return None;
+    }
+    Some((data[0], &data[header..header + len], &data[header + len..]))
+}
@@ -257,6 +257,9 @@ pub enum Error {
     Io(io::Error),
     /// An error converting between Postgres and Rust types.
     Conversion(Box<error::Error + Sync + Send>),
+    /// The query was canceled because it did not complete within its
+    /// timeout.
+    Timeout,
 }
 
 impl fmt::Display for Error {
@@ -266,6 +269,7 @@ impl fmt::Display for Error {
             Error::Db(ref err) => write!(fmt, ": {}", err),
             Error::Io(ref err) => write!(fmt, ": {}", err),
             Error::Conversion(ref err) => write!(fmt, ": {}", err),
+            Error::Timeout => Ok(()),
         }
     }
 }
@@ -276,6 +280,7 @@ impl error::Error for Error {
             Error::Db(_) => "Error reported by Postgres",
             Error::Io(_) => "Error communicating with the server",
             Error::Conversion(_) => "Error converting between Postgres and Rust types",
+            Error::Timeout => "The query timed out",
         }
     }
 
@@ -284,6 +289,7 @@ impl error::Error for Error {
             Error::Db(ref err) => Some(&**err),
             Error::Io(ref err) => Some(err),
             Error::Conversion(ref err) => Some(&**err),
+            Error::Timeout => None,
         }
     }
 }
@@ -0,0 +1,51 @@
+//! Selection of the hash used for `tls-server-end-point` channel binding.
+use der;
+
+/// A hash algorithm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EndPointHash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+// The DER encoded object identifiers of signature algorithms and the hashes
+// they use.
+const SIGNATURE_HASHES: &'static [(&'static [u8], EndPointHash)] = &[
+    // md5WithRSAEncryption
+    (b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x04", EndPointHash::Sha256),
+    // sha1WithRSAEncryption
+    (b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x05", EndPointHash::Sha256),
+    // sha256WithRSAEncryption
+    (b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b", EndPointHash::Sha256),
+    // sha384WithRSAEncryption
+    (b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0c", EndPointHash::Sha384),
+    // sha512WithRSAEncryption
+    (b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0d", EndPointHash::Sha512),
+    // ecdsa-with-SHA1
+    (b"\x2a\x86\x48\xce\x3d\x04\x01", EndPointHash::Sha256),
+    // ecdsa-with-SHA256
+    (b"\x2a\x86\x48\xce\x3d\x04\x03\x02", EndPointHash::Sha256),
+    // ecdsa-with-SHA384
+    (b"\x2a\x86\x48\xce\x3d\x04\x03\x03", EndPointHash::Sha384),
+    // ecdsa-with-SHA512
+    (b"\x2a\x86\x48\xce\x3d\x04\x03\x04", EndPointHash::Sha512),
+    // id-dsa-with-sha1
+    (b"\x2a\x86\x48\xce\x38\x04\x03", EndPointHash::Sha256),
+    // id-dsa-with-sha256
+    (b"\x60\x86\x48\x01\x65\x03\x04\x03\x02", EndPointHash::Sha256),
+];
+
+/// Returns the hash algorithm used to compute the `tls-server-end-point`
+/// channel binding data for the DER encoded certificate `cert`.
+///
+/// RFC 5929 specifies the hash algorithm of the certificate's signature,
+/// except that SHA-256 is used in place of MD5 and SHA-1. `None` is returned
+/// if the signature algorithm is not known to use one of these hashes, as
+/// with RSASSA-PSS and Ed25519, in which case the connection falls back to
+/// SCRAM authentication without channel binding.
+pub fn end_point_hash(cert: &[u8]) -> Option<EndPointHash> {
+    der::signature_algorithm(cert).and_then(|oid| {
+        SIGNATURE_HASHES.iter().find(|&&(o, _)| o == oid).map(|&(_, hash)| hash)
+    })
+}
@@ -4,11 +4,16 @@ pub use priv_io::Stream;
 use std::error::Error;
 use std::io::prelude::*;
 use std::fmt;
+use std::sync::Arc;
 
 #[cfg(feature = "openssl")]
-mod openssl;
+pub mod openssl;
+#[cfg(feature = "rustls")]
+pub mod rustls;
 #[cfg(feature = "security-framework")]
 mod security_framework;
+#[cfg(any(feature = "openssl", feature = "rustls", feature = "security-framework"))]
+mod end_point;
 
 /// A trait implemented by SSL adaptors.
 pub trait StreamWrapper: fmt::Debug + Read + Write + Send {
@@ -17,12 +22,48 @@ pub trait StreamWrapper: fmt::Debug + Read + Write + Send {
 
     /// Returns a mutable reference to the underlying `Stream`.
     fn get_mut(&mut self) -> &mut Stream;
+
+    /// Returns the `tls-server-end-point` channel binding data for the
+    /// session, as defined in RFC 5929.
+    ///
+    /// This is the hash of the DER encoded certificate presented by the
+    /// server. It is used with the SCRAM-SHA-256-PLUS authentication mechanism
+    /// to bind the authentication exchange to the TLS session, preventing a
+    /// man-in-the-middle with a different certificate from relaying it.
+    ///
+    /// The default implementation returns `None`, indicating that channel
+    /// binding is not supported.
+    fn tls_server_end_point(&self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// The ALPN protocol identifier used by direct SSL negotiation.
+pub const ALPN_PROTOCOL: &'static [u8] = b"postgresql";
+
+/// The verification of the server's certificate required by the SSL mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The SSL mode does not require verification. Whatever verification the
+    /// negotiator is configured to perform still applies.
+    Default,
+    /// The server's certificate must be signed by a trusted certificate
+    /// authority.
+    Ca,
+    /// Like `Ca`, but the server's certificate must also match the host name.
+    Full,
 }
 
 /// A trait implemented by types that can negotiate SSL over a Postgres stream.
 ///
 /// If the `openssl` Cargo feature is enabled, this trait will be implemented
-/// for `openssl::ssl::SslContext`.
+/// for `openssl::ssl::SslContext`. The `verify-ca` and `verify-full` SSL modes
+/// check the certificate chain against the context's trusted certificates.
+/// `openssl::OpenSsl` also implements it, configured from files as in libpq.
+///
+/// If the `rustls` Cargo feature is enabled, `rustls::Rustls` implements
+/// this trait without depending on a system SSL library. It also supports
+/// direct SSL negotiation.
 ///
 /// If the `security-framework` Cargo feature is enabled, this trait will be
 /// implemented for `security_framework::secure_transport::ClientBuilder`.
@@ -36,4 +77,45 @@ pub trait NegotiateSsl: fmt::Debug {
                      host: &str,
                      stream: Stream)
                      -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>>;
+
+    /// Negotiates an SSL session, verifying the server's certificate as
+    /// required by the `verify-ca` and `verify-full` SSL modes.
+    ///
+    /// The default implementation delegates to `negotiate_ssl` if no
+    /// verification is required and returns an error otherwise, since it
+    /// cannot know what the implementation verifies.
+    fn negotiate_ssl_verified(&self,
+                              host: &str,
+                              stream: Stream,
+                              verification: Verification)
+                              -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        match verification {
+            Verification::Default => self.negotiate_ssl(host, stream),
+            _ => Err("the SSL negotiator does not support certificate verification".into()),
+        }
+    }
+
+    /// Negotiates an SSL session immediately after connecting, as required by
+    /// `SslNegotiation::Direct`.
+    ///
+    /// The `ALPN_PROTOCOL` protocol must be offered, and the connection
+    /// rejected if the server does not select it. The default implementation
+    /// returns an error.
+    fn negotiate_ssl_direct(&self,
+                            _: &str,
+                            _: Stream,
+                            _: Verification)
+                            -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        Err("the SSL negotiator does not support direct SSL negotiation".into())
+    }
+
+    /// Returns a copy of the negotiator which can be shared between threads.
+    ///
+    /// A `CancelToken` uses it to send cancel requests over SSL after the
+    /// connection has been established. The default implementation returns
+    /// `None`, in which case cancel requests are sent without SSL, or fail if
+    /// the SSL mode requires SSL.
+    fn clone_shared(&self) -> Option<Arc<NegotiateSsl + Send + Sync>> {
+        None
+    }
 }
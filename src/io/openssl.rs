@@ -1,9 +1,139 @@
+//! OpenSSL support.
 extern crate openssl;
 
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::env;
 use std::error::Error;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::prelude::*;
+use std::net::IpAddr;
+use std::os::raw::{c_char, c_int, c_ulong, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Arc;
 
-use self::openssl::ssl::{SslContext, SslStream};
-use io::{StreamWrapper, Stream, NegotiateSsl};
+use self::openssl::crypto::hash::Type;
+use self::openssl::crypto::pkey::PKey;
+use self::openssl::nid::Nid;
+use self::openssl::ssl::{Ssl, SslContext, SslMethod, SslStream, SSL_VERIFY_PEER};
+use self::openssl::ssl::error::SslError;
+use self::openssl::x509::{X509, X509FileType, X509StoreContext};
+use SslFiles;
+use der;
+use io::{StreamWrapper, Stream, NegotiateSsl, Verification};
+use io::end_point::{self, EndPointHash};
+
+/// An SSL negotiator configured from files, as libpq is.
+///
+/// ```rust,no_run
+/// use postgres::{Connection, SslMode, IntoConnectParams};
+/// use postgres::io::openssl::OpenSsl;
+///
+/// let params = "host=db.example.com sslmode=verify-full sslcert=client.crt sslkey=client.key"
+///                  .into_connect_params()
+///                  .unwrap();
+/// let negotiator = OpenSsl::new(&params.ssl_files).unwrap();
+/// let conn = Connection::connect(params, SslMode::Prefer(&negotiator)).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenSsl {
+    context: SslContext,
+    has_root_cert: bool,
+}
+
+impl OpenSsl {
+    /// Creates a negotiator from the files specified by `files`.
+    ///
+    /// As in libpq, unspecified files default to those in `~/.postgresql`,
+    /// or `%APPDATA%\postgresql` on Windows, if they exist. If a root
+    /// certificate is present, the server's certificate is verified even if
+    /// the SSL mode does not require it.
+    ///
+    /// An error is returned if a specified file cannot be loaded, or if the
+    /// client certificate and private key do not match.
+    pub fn new(files: &SslFiles) -> Result<OpenSsl, Box<Error + Sync + Send>> {
+        let mut context = try!(SslContext::new(SslMethod::Sslv23));
+
+        let root_cert = file_or_default(&files.root_cert, "root.crt");
+        if let Some(ref path) = root_cert {
+            try!(context.set_CA_file(path)
+                        .map_err(|e| file_error("root certificate", path, &e)));
+        }
+
+        if let Some(cert) = file_or_default(&files.cert, "postgresql.crt") {
+            try!(context.set_certificate_chain_file(&cert, X509FileType::PEM)
+                        .map_err(|e| file_error("certificate", &cert, &e)));
+
+            let key = match files.key.clone().or_else(|| default_file("postgresql.key")) {
+                Some(key) => key,
+                None => {
+                    return Err("a client certificate was specified without a private key".into())
+                }
+            };
+            let key_data = try!(read_key(&key));
+            let password = files.key_password.as_ref().map(|p| &**p);
+            let pkey = try!(PKey::private_key_from_pem_cb(&mut &key_data[..],
+                                                          |buf| copy_password(password, buf))
+                                .map_err(|e| {
+                                    format!("unable to load private key file {} (if it is \
+                                             encrypted, a correct sslpassword must be \
+                                             specified): {}",
+                                            key.display(),
+                                            e)
+                                }));
+            try!(context.set_private_key(&pkey)
+                        .map_err(|e| file_error("private key", &key, &e)));
+            try!(context.check_private_key().map_err(|e| {
+                format!("certificate file {} does not match private key file {}: {}",
+                        cert.display(),
+                        key.display(),
+                        e)
+            }));
+        }
+
+        if let Some(path) = file_or_default(&files.crl, "root.crl") {
+            try!(load_crls(&context, &path));
+        }
+
+        Ok(OpenSsl {
+            context: context,
+            has_root_cert: root_cert.is_some(),
+        })
+    }
+}
+
+impl NegotiateSsl for OpenSsl {
+    fn negotiate_ssl(&self,
+                     host: &str,
+                     stream: Stream)
+                     -> Result<Box<StreamWrapper>, Box<Error + Send + Sync>> {
+        self.negotiate_ssl_verified(host, stream, Verification::Default)
+    }
+
+    fn negotiate_ssl_verified(&self,
+                              host: &str,
+                              stream: Stream,
+                              verification: Verification)
+                              -> Result<Box<StreamWrapper>, Box<Error + Send + Sync>> {
+        let verification = match verification {
+            Verification::Default if self.has_root_cert => Verification::Ca,
+            Verification::Default => Verification::Default,
+            _ if !self.has_root_cert => {
+                return Err("a root certificate file is required to verify the server's \
+                            certificate; specify one with sslrootcert or change sslmode"
+                               .into())
+            }
+            verification => verification,
+        };
+        connect(&self.context, host, stream, verification)
+    }
+
+    fn clone_shared(&self) -> Option<Arc<NegotiateSsl + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+}
 
 impl StreamWrapper for SslStream<Stream> {
     fn get_ref(&self) -> &Stream {
@@ -13,6 +143,20 @@ impl StreamWrapper for SslStream<Stream> {
     fn get_mut(&mut self) -> &mut Stream {
         self.get_mut()
     }
+
+    fn tls_server_end_point(&self) -> Option<Vec<u8>> {
+        let cert = match self.ssl().peer_certificate() {
+            Some(cert) => cert,
+            None => return None,
+        };
+        let hash = match to_der(&cert).and_then(|der| end_point::end_point_hash(&der)) {
+            Some(EndPointHash::Sha256) => Type::SHA256,
+            Some(EndPointHash::Sha384) => Type::SHA384,
+            Some(EndPointHash::Sha512) => Type::SHA512,
+            None => return None,
+        };
+        cert.fingerprint(hash)
+    }
 }
 
 impl NegotiateSsl for SslContext {
@@ -23,4 +167,241 @@ impl NegotiateSsl for SslContext {
         let stream = try!(SslStream::connect(self, stream));
         Ok(Box::new(stream))
     }
+
+    // The certificate chain is checked against the context's trusted
+    // certificates regardless of the context's verification mode.
+    fn negotiate_ssl_verified(&self,
+                              host: &str,
+                              stream: Stream,
+                              verification: Verification)
+                              -> Result<Box<StreamWrapper>, Box<Error + Send + Sync>> {
+        connect(self, host, stream, verification)
+    }
+
+    // Contexts are reference counted, so clones share their configuration.
+    fn clone_shared(&self) -> Option<Arc<NegotiateSsl + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+fn connect(context: &SslContext,
+           host: &str,
+           stream: Stream,
+           verification: Verification)
+           -> Result<Box<StreamWrapper>, Box<Error + Send + Sync>> {
+    let mut ssl = try!(Ssl::new(context));
+    let host = match verification {
+        Verification::Default => None,
+        Verification::Ca => Some(None),
+        Verification::Full => Some(Some(host.to_owned())),
+    };
+    if let Some(host) = host {
+        ssl.set_verify_callback(SSL_VERIFY_PEER, move |ok, x509_ctx| {
+            verify(host.as_ref().map(|h| &**h), ok, x509_ctx)
+        });
+    }
+    let stream = try!(SslStream::connect(ssl, stream));
+    Ok(Box::new(stream))
+}
+
+// Chain validation and revocation checks have already been performed by
+// OpenSSL, so only the host name of the server's own certificate needs to be
+// checked.
+fn verify(host: Option<&str>, preverify_ok: bool, x509_ctx: &X509StoreContext) -> bool {
+    if !preverify_ok {
+        return false;
+    }
+
+    let cert = match x509_ctx.get_current_cert() {
+        Some(cert) => cert,
+        None => return false,
+    };
+
+    match host {
+        Some(host) if x509_ctx.error_depth() == 0 => matches_host(host, &cert),
+        _ => true,
+    }
+}
+
+fn to_der(cert: &X509) -> Option<Vec<u8>> {
+    let mut pem = vec![];
+    if cert.write_pem(&mut pem).is_err() {
+        return None;
+    }
+    let pem = match String::from_utf8(pem) {
+        Ok(pem) => pem,
+        Err(_) => return None,
+    };
+    der::pem_blocks(&pem, "CERTIFICATE").ok().and_then(|b| b.into_iter().next())
+}
+
+// As in libpq, the common name is only consulted if the certificate has no
+// subject alternative names of the relevant type.
+fn matches_host(host: &str, cert: &X509) -> bool {
+    let ip = host.parse::<IpAddr>().ok();
+
+    if let Some(names) = cert.subject_alt_names() {
+        let mut found_name = false;
+        for name in names.iter() {
+            match ip {
+                Some(ref ip) => {
+                    if let Some(addr) = name.ipaddress() {
+                        found_name = true;
+                        if matches_ip(ip, addr) {
+                            return true;
+                        }
+                    }
+                }
+                None => {
+                    if let Some(pattern) = name.dnsname() {
+                        found_name = true;
+                        if matches_dns(host, pattern) {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        if found_name {
+            return false;
+        }
+    }
+
+    match cert.subject_name().text_by_nid(Nid::CN) {
+        Some(pattern) => {
+            match ip {
+                Some(_) => pattern.eq_ignore_ascii_case(host),
+                None => matches_dns(host, &pattern),
+            }
+        }
+        None => false,
+    }
+}
+
+fn matches_ip(ip: &IpAddr, addr: &[u8]) -> bool {
+    match *ip {
+        IpAddr::V4(ref ip) => &ip.octets()[..] == addr,
+        IpAddr::V6(ref ip) => &ip.octets()[..] == addr,
+    }
+}
+
+// A leading `*.` in the certificate's name matches exactly one label.
+fn matches_dns(host: &str, pattern: &str) -> bool {
+    let host = host.trim_right_matches('.');
+    let pattern = pattern.trim_right_matches('.');
+
+    if pattern.starts_with("*.") {
+        match host.find('.') {
+            Some(i) => i > 0 && host[i..].eq_ignore_ascii_case(&pattern[1..]),
+            None => false,
+        }
+    } else {
+        host.eq_ignore_ascii_case(pattern)
+    }
+}
+
+fn file_or_default(path: &Option<PathBuf>, name: &str) -> Option<PathBuf> {
+    match *path {
+        Some(ref path) => Some(path.clone()),
+        None => default_file(name).and_then(|p| if p.exists() { Some(p) } else { None }),
+    }
+}
+
+fn default_file(name: &str) -> Option<PathBuf> {
+    default_dir().map(|dir| dir.join(name))
+}
+
+#[cfg(unix)]
+#[allow(deprecated)]
+fn default_dir() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".postgresql"))
+}
+
+#[cfg(windows)]
+fn default_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("postgresql"))
+}
+
+fn file_error(kind: &str, path: &Path, err: &Error) -> Box<Error + Sync + Send> {
+    format!("unable to load {} file {}: {}", kind, path.display(), err).into()
+}
+
+fn read_key(path: &Path) -> Result<Vec<u8>, Box<Error + Sync + Send>> {
+    let mut file = try!(File::open(path).map_err(|e| file_error("private key", path, &e)));
+    try!(check_key_permissions(path, &file));
+    let mut data = vec![];
+    try!(file.read_to_end(&mut data).map_err(|e| file_error("private key", path, &e)));
+    Ok(data)
+}
+
+// Like libpq, refuse to use a key file accessible by anyone other than its
+// owner, or by group members if owned by root.
+#[cfg(unix)]
+fn check_key_permissions(path: &Path, file: &File) -> Result<(), Box<Error + Sync + Send>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = try!(file.metadata());
+    let allowed = if metadata.uid() == 0 { 0o640 } else { 0o600 };
+    if metadata.mode() & 0o777 & !allowed != 0 {
+        return Err(format!("private key file {} has group or world access; permissions should \
+                            be u=rw (0600) or less",
+                           path.display())
+                       .into());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn check_key_permissions(_: &Path, _: &File) -> Result<(), Box<Error + Sync + Send>> {
+    Ok(())
+}
+
+// Returns the number of bytes written, which is zero if no password was
+// specified.
+fn copy_password(password: Option<&str>, buf: &mut [c_char]) -> usize {
+    let password = match password {
+        Some(password) => password.as_bytes(),
+        None => return 0,
+    };
+    let len = cmp::min(password.len(), buf.len());
+    for (dst, &src) in buf.iter_mut().zip(&password[..len]) {
+        *dst = src as c_char;
+    }
+    len
+}
+
+// Not exposed by openssl-sys, but provided by the library it links to.
+extern "C" {
+    fn SSL_CTX_get_cert_store(ctx: *const c_void) -> *mut c_void;
+    fn X509_STORE_load_locations(store: *mut c_void,
+                                 file: *const c_char,
+                                 dir: *const c_char)
+                                 -> c_int;
+    fn X509_STORE_set_flags(store: *mut c_void, flags: c_ulong) -> c_int;
+}
+
+const X509_V_FLAG_CRL_CHECK: c_ulong = 0x4;
+const X509_V_FLAG_CRL_CHECK_ALL: c_ulong = 0x8;
+
+// As in libpq, the CRLs are added to the context's certificate store and
+// OpenSSL checks every certificate in the chain against them, verifying the
+// lists' signatures and update times.
+fn load_crls(context: &SslContext, path: &Path) -> Result<(), Box<Error + Sync + Send>> {
+    let c_path = match path.to_str().and_then(|p| CString::new(p).ok()) {
+        Some(c_path) => c_path,
+        None => {
+            return Err(format!("invalid certificate revocation list file path {}",
+                               path.display())
+                           .into())
+        }
+    };
+
+    unsafe {
+        let store = SSL_CTX_get_cert_store(context.get_ssl_context() as *const c_void);
+        if X509_STORE_load_locations(store, c_path.as_ptr(), ptr::null()) != 1 {
+            return Err(file_error("certificate revocation list", path, &SslError::get()));
+        }
+        X509_STORE_set_flags(store, X509_V_FLAG_CRL_CHECK | X509_V_FLAG_CRL_CHECK_ALL);
+    }
+    Ok(())
 }
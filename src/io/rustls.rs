@@ -0,0 +1,318 @@
+//! Rustls support.
+extern crate ring;
+extern crate rustls;
+extern crate rustls_native_certs;
+extern crate webpki;
+extern crate webpki_roots;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::io::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+
+use self::ring::digest;
+use self::rustls::{Certificate, ClientConfig, ClientSession, RootCertStore, ServerCertVerified,
+                   ServerCertVerifier, Session, StreamOwned, TLSError, WebPKIVerifier};
+use self::webpki::DNSNameRef;
+use io::{NegotiateSsl, Stream, StreamWrapper, Verification, ALPN_PROTOCOL};
+use io::end_point::{self, EndPointHash};
+
+/// An SSL negotiator implemented with rustls.
+///
+/// The server's certificate is verified against the root certificates the
+/// negotiator was created with in every SSL mode, so the `prefer` and
+/// `require` modes behave like `verify-ca`, in the same way as libpq when a
+/// root certificate file is present. A negotiator created with `unverified`
+/// accepts any certificate, and can only be used in the `prefer` and `require`
+/// SSL modes.
+///
+/// The host passed to `negotiate_ssl` is sent as the SNI server name if it is
+/// a DNS name. Hosts which are not, such as IP addresses, are only supported if
+/// the SSL mode is not `verify-full`, since the server's certificate can only
+/// be matched against DNS names.
+///
+/// Direct SSL negotiation is supported.
+///
+/// ```rust,no_run
+/// use postgres::{Connection, SslMode};
+/// use postgres::io::rustls::Rustls;
+///
+/// let negotiator = Rustls::with_webpki_roots();
+/// let conn = Connection::connect("postgres://postgres@db.example.com",
+///                                SslMode::VerifyFull(&negotiator))
+///                .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Rustls {
+    config: ClientConfig,
+    verifier: VerifierKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VerifierKind {
+    Roots,
+    Config,
+    Disabled,
+}
+
+impl fmt::Debug for Rustls {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Rustls")
+           .field("root_certs", &self.config.root_store.len())
+           .field("verifier", &self.verifier)
+           .finish()
+    }
+}
+
+impl Rustls {
+    /// Creates a negotiator trusting the Mozilla root certificates bundled
+    /// with the `webpki-roots` crate.
+    pub fn with_webpki_roots() -> Rustls {
+        let mut config = ClientConfig::new();
+        config.root_store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        Rustls::with_roots(config)
+    }
+
+    /// Creates a negotiator trusting the platform's root certificates.
+    pub fn with_native_roots() -> Result<Rustls, Box<Error + Sync + Send>> {
+        let mut config = ClientConfig::new();
+        config.root_store = match rustls_native_certs::load_native_certs() {
+            Ok(root_store) => root_store,
+            Err((_, err)) => return Err(err.into()),
+        };
+        if config.root_store.is_empty() {
+            return Err("the platform has no root certificates".into());
+        }
+        Ok(Rustls::with_roots(config))
+    }
+
+    /// Creates a negotiator trusting the PEM encoded certificates in the
+    /// file at `path`.
+    pub fn with_root_cert_file<P>(path: P) -> Result<Rustls, Box<Error + Sync + Send>>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let file = try!(File::open(path).map_err(|e| {
+            format!("unable to load root certificate file {}: {}", path.display(), e)
+        }));
+
+        let mut config = ClientConfig::new();
+        match config.root_store.add_pem_file(&mut BufReader::new(file)) {
+            Ok((valid, _)) if valid > 0 => Ok(Rustls::with_roots(config)),
+            _ => {
+                Err(format!("root certificate file {} contains no valid certificates",
+                            path.display())
+                        .into())
+            }
+        }
+    }
+
+    /// Creates a negotiator which does not verify the server's certificate.
+    ///
+    /// The connection is encrypted but not authenticated, so it is open to
+    /// man-in-the-middle attacks. The negotiator can only be used in the
+    /// `prefer` and `require` SSL modes.
+    pub fn unverified() -> Rustls {
+        let mut config = ClientConfig::new();
+        config.dangerous().set_certificate_verifier(Arc::new(NoVerifier));
+        Rustls {
+            config: config,
+            verifier: VerifierKind::Disabled,
+        }
+    }
+
+    /// Creates a negotiator from an existing configuration.
+    ///
+    /// The configuration's certificate verifier is used as-is in every SSL
+    /// mode. The default verifier checks the server's name as well as its
+    /// certificate chain, so `verify-ca` is as strict as `verify-full`.
+    pub fn from_config(config: ClientConfig) -> Rustls {
+        Rustls {
+            config: config,
+            verifier: VerifierKind::Config,
+        }
+    }
+
+    fn with_roots(config: ClientConfig) -> Rustls {
+        Rustls {
+            config: config,
+            verifier: VerifierKind::Roots,
+        }
+    }
+}
+
+impl NegotiateSsl for Rustls {
+    fn negotiate_ssl(&self,
+                     host: &str,
+                     stream: Stream)
+                     -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        self.negotiate_ssl_verified(host, stream, Verification::Default)
+    }
+
+    fn negotiate_ssl_verified(&self,
+                              host: &str,
+                              stream: Stream,
+                              verification: Verification)
+                              -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        self.connect(host, stream, verification, false)
+    }
+
+    fn negotiate_ssl_direct(&self,
+                            host: &str,
+                            stream: Stream,
+                            verification: Verification)
+                            -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        self.connect(host, stream, verification, true)
+    }
+
+    fn clone_shared(&self) -> Option<Arc<NegotiateSsl + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+impl Rustls {
+    fn connect(&self,
+               host: &str,
+               stream: Stream,
+               verification: Verification,
+               direct: bool)
+               -> Result<Box<StreamWrapper>, Box<Error + Sync + Send>> {
+        let mut config = self.config.clone();
+        match (self.verifier, verification) {
+            (VerifierKind::Disabled, Verification::Default) |
+            (VerifierKind::Config, _) |
+            (VerifierKind::Roots, Verification::Full) => {}
+            (VerifierKind::Disabled, _) => {
+                return Err("an unverified negotiator cannot verify the server's certificate; \
+                            create one with root certificates or change sslmode"
+                               .into())
+            }
+            (VerifierKind::Roots, _) => {
+                config.dangerous()
+                      .set_certificate_verifier(Arc::new(CaVerifier(WebPKIVerifier::new())))
+            }
+        }
+        if direct {
+            config.set_protocols(&[ALPN_PROTOCOL.to_vec()]);
+        }
+
+        // The name is only checked in the verify-full SSL mode, so other
+        // hosts can be used without SNI. A custom verifier may still reject
+        // them.
+        let dns_name = match DNSNameRef::try_from_ascii_str(host) {
+            Ok(dns_name) => dns_name,
+            Err(_) if verification != Verification::Full => {
+                config.enable_sni = false;
+                DNSNameRef::try_from_ascii_str("invalid").unwrap()
+            }
+            Err(_) => {
+                return Err(format!("`{}` is not a valid DNS name, which the verify-full SSL \
+                                    mode requires",
+                                   host)
+                               .into())
+            }
+        };
+        let session = ClientSession::new(&Arc::new(config), dns_name);
+
+        // Complete the handshake here so that failures are reported as SSL
+        // errors.
+        let mut stream = StreamOwned::new(session, stream);
+        while stream.sess.is_handshaking() {
+            try!(stream.sess.complete_io(&mut stream.sock));
+        }
+
+        if direct && stream.sess.get_alpn_protocol() != Some(ALPN_PROTOCOL) {
+            return Err("the server did not select the postgresql ALPN protocol".into());
+        }
+
+        Ok(Box::new(RustlsStream(stream)))
+    }
+}
+
+struct RustlsStream(StreamOwned<ClientSession, Stream>);
+
+impl fmt::Debug for RustlsStream {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RustlsStream").field(&self.0.sock).finish()
+    }
+}
+
+impl Read for RustlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for RustlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl StreamWrapper for RustlsStream {
+    fn get_ref(&self) -> &Stream {
+        &self.0.sock
+    }
+
+    fn get_mut(&mut self) -> &mut Stream {
+        &mut self.0.sock
+    }
+
+    fn tls_server_end_point(&self) -> Option<Vec<u8>> {
+        let certs = match self.0.sess.get_peer_certificates() {
+            Some(certs) => certs,
+            None => return None,
+        };
+        let cert = match certs.first() {
+            Some(cert) => &cert.0,
+            None => return None,
+        };
+        let algorithm = match end_point::end_point_hash(cert) {
+            Some(EndPointHash::Sha256) => &digest::SHA256,
+            Some(EndPointHash::Sha384) => &digest::SHA384,
+            Some(EndPointHash::Sha512) => &digest::SHA512,
+            None => return None,
+        };
+        Some(digest::digest(algorithm, cert).as_ref().to_vec())
+    }
+}
+
+struct NoVerifier;
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(&self,
+                          _: &RootCertStore,
+                          _: &[Certificate],
+                          _: DNSNameRef,
+                          _: &[u8])
+                          -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+// webpki validates the certificate chain before the name, so a name mismatch
+// means the chain itself is trusted.
+struct CaVerifier(WebPKIVerifier);
+
+impl ServerCertVerifier for CaVerifier {
+    fn verify_server_cert(&self,
+                          roots: &RootCertStore,
+                          presented_certs: &[Certificate],
+                          dns_name: DNSNameRef,
+                          ocsp_response: &[u8])
+                          -> Result<ServerCertVerified, TLSError> {
+        match self.0.verify_server_cert(roots, presented_certs, dns_name, ocsp_response) {
+            Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName)) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            result => result,
+        }
+    }
+}
@@ -1,8 +1,18 @@
 extern crate security_framework;
 
+use libc::c_void;
 use self::security_framework::secure_transport::{SslStream, ClientBuilder};
-use io::{Stream, StreamWrapper, NegotiateSsl};
+use io::{Stream, StreamWrapper, NegotiateSsl, Verification};
+use io::end_point::{self, EndPointHash};
 use std::error::Error;
+use std::sync::Arc;
+
+// CommonCrypto is part of libSystem.
+extern "C" {
+    fn CC_SHA256(data: *const c_void, len: u32, md: *mut u8) -> *mut u8;
+    fn CC_SHA384(data: *const c_void, len: u32, md: *mut u8) -> *mut u8;
+    fn CC_SHA512(data: *const c_void, len: u32, md: *mut u8) -> *mut u8;
+}
 
 impl StreamWrapper for SslStream<Stream> {
     fn get_ref(&self) -> &Stream {
@@ -12,6 +22,33 @@ impl StreamWrapper for SslStream<Stream> {
     fn get_mut(&mut self) -> &mut Stream {
         self.get_mut()
     }
+
+    fn tls_server_end_point(&self) -> Option<Vec<u8>> {
+        let trust = match self.context().peer_trust() {
+            Ok(trust) => trust,
+            Err(_) => return None,
+        };
+        let cert = match trust.certificate_at_index(0) {
+            Some(cert) => cert.to_der(),
+            None => return None,
+        };
+        if cert.len() > u32::max_value() as usize {
+            return None;
+        }
+
+        let (f, len): (unsafe extern "C" fn(*const c_void, u32, *mut u8) -> *mut u8, usize) =
+            match end_point::end_point_hash(&cert) {
+                Some(EndPointHash::Sha256) => (CC_SHA256, 32),
+                Some(EndPointHash::Sha384) => (CC_SHA384, 48),
+                Some(EndPointHash::Sha512) => (CC_SHA512, 64),
+                None => return None,
+            };
+        let mut hash = vec![0; len];
+        unsafe {
+            f(cert.as_ptr() as *const c_void, cert.len() as u32, hash.as_mut_ptr());
+        }
+        Some(hash)
+    }
 }
 
 impl NegotiateSsl for ClientBuilder {
@@ -22,4 +59,17 @@ impl NegotiateSsl for ClientBuilder {
         let stream = try!(self.handshake(domain, stream));
         Ok(Box::new(stream))
     }
+
+    // Secure Transport always validates the certificate chain and host name.
+    fn negotiate_ssl_verified(&self,
+                              domain: &str,
+                              stream: Stream,
+                              _: Verification)
+                              -> Result<Box<StreamWrapper>, Box<Error + Send + Sync>> {
+        self.negotiate_ssl(domain, stream)
+    }
+
+    fn clone_shared(&self) -> Option<Arc<NegotiateSsl + Send + Sync>> {
+        Some(Arc::new(self.clone()))
+    }
 }
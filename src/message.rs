@@ -23,6 +23,15 @@ pub enum BackendMessage {
         salt: [u8; 4],
     },
     AuthenticationOk,
+    AuthenticationSASL {
+        mechanisms: Vec<String>,
+    },
+    AuthenticationSASLContinue {
+        data: Vec<u8>,
+    },
+    AuthenticationSASLFinal {
+        data: Vec<u8>,
+    },
     AuthenticationSCMCredential,
     AuthenticationSSPI,
     BackendKeyData {
@@ -122,6 +131,7 @@ pub enum FrontendMessage<'a> {
         portal: &'a str,
         max_rows: i32,
     },
+    Flush,
     Parse {
         name: &'a str,
         query: &'a str,
@@ -133,6 +143,13 @@ pub enum FrontendMessage<'a> {
     Query {
         query: &'a str,
     },
+    SASLInitialResponse {
+        mechanism: &'a str,
+        data: &'a [u8],
+    },
+    SASLResponse {
+        data: &'a [u8],
+    },
     SslRequest {
         code: u32,
     },
@@ -222,6 +239,7 @@ impl<W: Write> WriteMessage for W {
                 try!(buf.write_cstr(portal));
                 try!(buf.write_i32::<BigEndian>(max_rows));
             }
+            Flush => ident = Some(b'H'),
             Parse { name, query, param_types } => {
                 ident = Some(b'P');
                 try!(buf.write_cstr(name));
@@ -239,6 +257,16 @@ impl<W: Write> WriteMessage for W {
                 ident = Some(b'Q');
                 try!(buf.write_cstr(query));
             }
+            SASLInitialResponse { mechanism, data } => {
+                ident = Some(b'p');
+                try!(buf.write_cstr(mechanism));
+                try!(buf.write_i32::<BigEndian>(try!(i32::from_usize(data.len()))));
+                try!(buf.write_all(data));
+            }
+            SASLResponse { data } => {
+                ident = Some(b'p');
+                try!(buf.write_all(data));
+            }
             StartupMessage { version, parameters } => {
                 try!(buf.write_u32::<BigEndian>(version));
                 for &(ref k, ref v) in parameters {
@@ -335,82 +363,108 @@ impl<R: BufRead + StreamOptions> ReadMessage for R {
     }
 
     fn finish_read_message(&mut self, ident: u8) -> io::Result<BackendMessage> {
-        // subtract size of length value
-        let len = try!(self.read_u32::<BigEndian>()) - mem::size_of::<u32>() as u32;
-        let mut rdr = self.by_ref().take(len as u64);
-
-        let ret = match ident {
-            b'1' => ParseComplete,
-            b'2' => BindComplete,
-            b'3' => CloseComplete,
-            b'A' => {
-                NotificationResponse {
-                    pid: try!(rdr.read_u32::<BigEndian>()),
-                    channel: try!(rdr.read_cstr()),
-                    payload: try!(rdr.read_cstr()),
-                }
+        read_message_body(self, ident)
+    }
+}
+
+/// Parses a backend message from the start of `buf`.
+///
+/// Returns the message along with the number of bytes it occupied, or `None`
+/// if `buf` does not yet contain an entire message.
+#[cfg(feature = "futures")]
+pub fn parse_message(buf: &[u8]) -> io::Result<Option<(BackendMessage, usize)>> {
+    if buf.len() < 1 + mem::size_of::<u32>() {
+        return Ok(None);
+    }
+
+    let len = try!((&buf[1..]).read_u32::<BigEndian>()) as usize;
+    if len < mem::size_of::<u32>() {
+        return Err(io::Error::new(io::ErrorKind::Other, "invalid message length"));
+    }
+    if buf.len() < 1 + len {
+        return Ok(None);
+    }
+
+    let message = try!(read_message_body(&mut &buf[1..1 + len], buf[0]));
+    Ok(Some((message, 1 + len)))
+}
+
+fn read_message_body<R: BufRead>(buf: &mut R, ident: u8) -> io::Result<BackendMessage> {
+    // subtract size of length value
+    let len = try!(buf.read_u32::<BigEndian>()) - mem::size_of::<u32>() as u32;
+    let mut rdr = buf.take(len as u64);
+
+    let ret = match ident {
+        b'1' => ParseComplete,
+        b'2' => BindComplete,
+        b'3' => CloseComplete,
+        b'A' => {
+            NotificationResponse {
+                pid: try!(rdr.read_u32::<BigEndian>()),
+                channel: try!(rdr.read_cstr()),
+                payload: try!(rdr.read_cstr()),
             }
-            b'c' => BCopyDone,
-            b'C' => CommandComplete { tag: try!(rdr.read_cstr()) },
-            b'd' => {
-                let mut data = vec![];
-                try!(rdr.read_to_end(&mut data));
-                BCopyData { data: data }
+        }
+        b'c' => BCopyDone,
+        b'C' => CommandComplete { tag: try!(rdr.read_cstr()) },
+        b'd' => {
+            let mut data = vec![];
+            try!(rdr.read_to_end(&mut data));
+            BCopyData { data: data }
+        }
+        b'D' => try!(read_data_row(&mut rdr)),
+        b'E' => ErrorResponse { fields: try!(read_fields(&mut rdr)) },
+        b'G' => {
+            let format = try!(rdr.read_u8());
+            let mut column_formats = vec![];
+            for _ in 0..try!(rdr.read_u16::<BigEndian>()) {
+                column_formats.push(try!(rdr.read_u16::<BigEndian>()));
             }
-            b'D' => try!(read_data_row(&mut rdr)),
-            b'E' => ErrorResponse { fields: try!(read_fields(&mut rdr)) },
-            b'G' => {
-                let format = try!(rdr.read_u8());
-                let mut column_formats = vec![];
-                for _ in 0..try!(rdr.read_u16::<BigEndian>()) {
-                    column_formats.push(try!(rdr.read_u16::<BigEndian>()));
-                }
-                CopyInResponse {
-                    format: format,
-                    column_formats: column_formats,
-                }
+            CopyInResponse {
+                format: format,
+                column_formats: column_formats,
             }
-            b'H' => {
-                let format = try!(rdr.read_u8());
-                let mut column_formats = vec![];
-                for _ in 0..try!(rdr.read_u16::<BigEndian>()) {
-                    column_formats.push(try!(rdr.read_u16::<BigEndian>()));
-                }
-                CopyOutResponse {
-                    format: format,
-                    column_formats: column_formats,
-                }
+        }
+        b'H' => {
+            let format = try!(rdr.read_u8());
+            let mut column_formats = vec![];
+            for _ in 0..try!(rdr.read_u16::<BigEndian>()) {
+                column_formats.push(try!(rdr.read_u16::<BigEndian>()));
             }
-            b'I' => EmptyQueryResponse,
-            b'K' => {
-                BackendKeyData {
-                    process_id: try!(rdr.read_u32::<BigEndian>()),
-                    secret_key: try!(rdr.read_u32::<BigEndian>()),
-                }
+            CopyOutResponse {
+                format: format,
+                column_formats: column_formats,
             }
-            b'n' => NoData,
-            b'N' => NoticeResponse { fields: try!(read_fields(&mut rdr)) },
-            b'R' => try!(read_auth_message(&mut rdr)),
-            b's' => PortalSuspended,
-            b'S' => {
-                ParameterStatus {
-                    parameter: try!(rdr.read_cstr()),
-                    value: try!(rdr.read_cstr()),
-                }
+        }
+        b'I' => EmptyQueryResponse,
+        b'K' => {
+            BackendKeyData {
+                process_id: try!(rdr.read_u32::<BigEndian>()),
+                secret_key: try!(rdr.read_u32::<BigEndian>()),
             }
-            b't' => try!(read_parameter_description(&mut rdr)),
-            b'T' => try!(read_row_description(&mut rdr)),
-            b'Z' => ReadyForQuery { _state: try!(rdr.read_u8()) },
-            t => {
-                return Err(io::Error::new(io::ErrorKind::Other,
-                                          format!("unexpected message tag `{}`", t)))
+        }
+        b'n' => NoData,
+        b'N' => NoticeResponse { fields: try!(read_fields(&mut rdr)) },
+        b'R' => try!(read_auth_message(&mut rdr)),
+        b's' => PortalSuspended,
+        b'S' => {
+            ParameterStatus {
+                parameter: try!(rdr.read_cstr()),
+                value: try!(rdr.read_cstr()),
             }
-        };
-        if rdr.limit() != 0 {
-            return Err(io::Error::new(io::ErrorKind::Other, "didn't read entire message"));
         }
-        Ok(ret)
+        b't' => try!(read_parameter_description(&mut rdr)),
+        b'T' => try!(read_row_description(&mut rdr)),
+        b'Z' => ReadyForQuery { _state: try!(rdr.read_u8()) },
+        t => {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("unexpected message tag `{}`", t)))
+        }
+    };
+    if rdr.limit() != 0 {
+        return Err(io::Error::new(io::ErrorKind::Other, "didn't read entire message"));
     }
+    Ok(ret)
 }
 
 fn read_fields<R: BufRead>(buf: &mut R) -> io::Result<Vec<(u8, String)>> {
@@ -446,7 +500,7 @@ fn read_data_row<R: BufRead>(buf: &mut R) -> io::Result<BackendMessage> {
     Ok(DataRow { row: values })
 }
 
-fn read_auth_message<R: Read>(buf: &mut R) -> io::Result<BackendMessage> {
+fn read_auth_message<R: BufRead>(buf: &mut R) -> io::Result<BackendMessage> {
     Ok(match try!(buf.read_i32::<BigEndian>()) {
         0 => AuthenticationOk,
         2 => AuthenticationKerberosV5,
@@ -459,6 +513,27 @@ fn read_auth_message<R: Read>(buf: &mut R) -> io::Result<BackendMessage> {
         6 => AuthenticationSCMCredential,
         7 => AuthenticationGSS,
         9 => AuthenticationSSPI,
+        10 => {
+            let mut mechanisms = vec![];
+            loop {
+                let mechanism = try!(buf.read_cstr());
+                if mechanism.is_empty() {
+                    break;
+                }
+                mechanisms.push(mechanism);
+            }
+            AuthenticationSASL { mechanisms: mechanisms }
+        }
+        11 => {
+            let mut data = vec![];
+            try!(buf.read_to_end(&mut data));
+            AuthenticationSASLContinue { data: data }
+        }
+        12 => {
+            let mut data = vec![];
+            try!(buf.read_to_end(&mut data));
+            AuthenticationSASLFinal { data: data }
+        }
         t => {
             return Err(io::Error::new(io::ErrorKind::Other,
                                       format!("unexpected authentication tag `{}`", t)))
@@ -0,0 +1,1470 @@
+//! Non-blocking connections driven by futures.
+//!
+//! An `AsyncConnection` speaks the same protocol as `Connection`, but runs
+//! over any stream implementing the `AsyncRead` and `AsyncWrite` traits from
+//! the `futures-io` crate, so it can be used with any executor. Each
+//! operation returns a future which borrows the connection, so only one
+//! operation may be in progress at a time.
+//!
+//! The connection does not open sockets itself. The caller connects the
+//! stream with their runtime's networking types and passes it to
+//! `AsyncConnection::connect`. To use SSL, wrap the stream with an
+//! asynchronous TLS implementation advertising the `io::ALPN_PROTOCOL` ALPN
+//! protocol, as the `sslnegotiation=direct` connection parameter would.
+//!
+//! Dropping a future before it completes abandons the operation. Its
+//! remaining responses are discarded by the next operation, so the
+//! connection stays usable, but the operation may or may not have taken
+//! effect.
+//!
+//! This module requires the `futures` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! extern crate async_io;
+//! extern crate postgres;
+//!
+//! use async_io::{block_on, Async};
+//! use postgres::nonblocking::AsyncConnection;
+//! use std::net::TcpStream;
+//!
+//! fn main() {
+//!     let stream = block_on(Async::<TcpStream>::connect(([127, 0, 0, 1], 5432))).unwrap();
+//!     let mut conn = block_on(AsyncConnection::connect(stream, "postgresql://postgres@localhost"))
+//!                        .unwrap();
+//!     let stmt = block_on(conn.prepare("SELECT $1::INT")).unwrap();
+//!     for row in block_on(conn.query(&stmt, &[&1i32])).unwrap() {
+//!         let value: i32 = row.get(0);
+//!         println!("{}", value);
+//!     }
+//! }
+//! ```
+extern crate futures_core;
+extern crate futures_io;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::result;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use self::futures_core::Stream;
+use self::futures_io::{AsyncRead, AsyncWrite};
+
+use {bad_response, desynchronized, startup_options, startup_user, Authenticator,
+     TYPEINFO_FALLBACK_SQL, TYPEINFO_SQL};
+use {CancelData, ColumnNew, ConnectParams, DbErrorNew, HandleNotice,
+     IntoConnectParams, LoggingNoticeHandler, OtherNew, Result, SessionInfoNew, StatementInfo,
+     UserInfo};
+use error::{ConnectError, DbError, Error, SqlState};
+use message::{self, BackendMessage, FrontendMessage, RowDescriptionEntry, WriteMessage};
+use message::BackendMessage::*;
+use message::FrontendMessage::*;
+use notification::Notification;
+use stmt::Column;
+use types::{FromSql, Kind, Oid, Other, SessionInfo, ToSql, Type, WrongType};
+use util;
+use WrongTypeNew;
+
+macro_rules! try_poll {
+    ($e:expr) => (
+        match $e {
+            Poll::Ready(Ok(v)) => v,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(::std::convert::From::from(e))),
+            Poll::Pending => return Poll::Pending,
+        }
+    )
+}
+
+macro_rules! try_result {
+    ($e:expr) => (
+        match $e {
+            Ok(v) => v,
+            Err(e) => return Poll::Ready(Err(::std::convert::From::from(e))),
+        }
+    )
+}
+
+/// A non-blocking connection to a Postgres database.
+pub struct AsyncConnection<S> {
+    stream: S,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    write_buf: Vec<u8>,
+    notice_handler: Box<HandleNotice>,
+    notifications: VecDeque<Notification>,
+    cancel_data: CancelData,
+    unknown_types: HashMap<Oid, Other>,
+    typeinfo_query: &'static str,
+    parameters: Arc<HashMap<String, String>>,
+    next_stmt_id: u32,
+    trans_depth: u32,
+    abandoned: u32,
+    desynchronized: bool,
+}
+
+impl<S> fmt::Debug for AsyncConnection<S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncConnection")
+           .field("cancel_data", &self.cancel_data)
+           .field("notifications", &self.notifications.len())
+           .field("transaction_depth", &self.trans_depth)
+           .field("desynchronized", &self.desynchronized)
+           .finish()
+    }
+}
+
+impl<S> AsyncConnection<S> {
+    /// Returns information used to cancel pending queries.
+    ///
+    /// See the `cancel_query` function.
+    pub fn cancel_data(&self) -> CancelData {
+        self.cancel_data
+    }
+
+    /// Returns the value of the specified Postgres backend parameter, such as
+    /// `timezone` or `server_version`.
+    pub fn parameter(&self, param: &str) -> Option<&str> {
+        self.parameters.get(param).map(|s| &**s)
+    }
+
+    /// Sets the notice handler for the connection, returning the old handler.
+    pub fn set_notice_handler(&mut self, handler: Box<HandleNotice>) -> Box<HandleNotice> {
+        mem::replace(&mut self.notice_handler, handler)
+    }
+
+    /// Returns whether or not the stream has been desynchronized due to an
+    /// error in the communication channel with the server.
+    ///
+    /// If this has occurred, all further operations will fail.
+    pub fn is_desynchronized(&self) -> bool {
+        self.desynchronized
+    }
+
+    /// Returns the number of transactions currently open on the connection.
+    pub fn transaction_depth(&self) -> u32 {
+        self.trans_depth
+    }
+
+    fn send(&mut self, messages: &[FrontendMessage]) -> io::Result<()> {
+        for message in messages {
+            try!(self.write_buf.write_message(message));
+        }
+        Ok(())
+    }
+
+    fn make_stmt_name(&mut self) -> String {
+        let stmt_name = format!("s{}", self.next_stmt_id);
+        self.next_stmt_id += 1;
+        stmt_name
+    }
+
+    fn cached_type(&self, oid: Oid) -> Option<Type> {
+        match Type::from_oid(oid) {
+            Some(ty) => Some(ty),
+            None => self.unknown_types.get(&oid).map(|ty| Type::Other(ty.clone())),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncConnection<S> {
+    /// Performs the startup handshake over a stream connected to a Postgres
+    /// server.
+    ///
+    /// The user, password, database and `options` of the connection
+    /// parameters are used as described in `Connection::connect`. Parameters
+    /// controlling how the stream is established, such as the host, SSL
+    /// settings and timeouts, are ignored.
+    ///
+    /// Channel binding is not supported since the stream is opaque to the
+    /// connection, so `channel_binding=require` will cause authentication to
+    /// fail.
+    pub fn connect<T>(stream: S, params: T) -> Connect<S>
+        where T: IntoConnectParams
+    {
+        let mut conn = AsyncConnection {
+            stream: stream,
+            read_buf: vec![],
+            read_pos: 0,
+            write_buf: vec![],
+            notice_handler: Box::new(LoggingNoticeHandler),
+            notifications: VecDeque::new(),
+            cancel_data: CancelData {
+                process_id: 0,
+                secret_key: 0,
+            },
+            unknown_types: HashMap::new(),
+            typeinfo_query: TYPEINFO_SQL,
+            parameters: Arc::new(HashMap::new()),
+            next_stmt_id: 0,
+            trans_depth: 0,
+            abandoned: 0,
+            desynchronized: false,
+        };
+
+        let handshake = params.into_connect_params()
+                              .map_err(ConnectError::ConnectParams)
+                              .and_then(|params| {
+                                  let user = try!(startup_user(&params));
+                                  let options = startup_options(&params, &user);
+                                  try!(conn.send(&[StartupMessage {
+                                                       version: message::PROTOCOL_VERSION,
+                                                       parameters: &options,
+                                                   }]));
+                                  Ok(Handshake {
+                                      conn: conn,
+                                      params: params,
+                                      user: user,
+                                      state: HandshakeState::Authenticating(Authenticator::new()),
+                                  })
+                              });
+
+        match handshake {
+            Ok(handshake) => {
+                Connect {
+                    handshake: Some(handshake),
+                    error: None,
+                }
+            }
+            Err(err) => {
+                Connect {
+                    handshake: None,
+                    error: Some(err),
+                }
+            }
+        }
+    }
+
+    /// Creates a new prepared statement.
+    ///
+    /// The statement remains prepared in the session until it is passed to
+    /// `close` or the connection is closed.
+    pub fn prepare<'a>(&'a mut self, query: &str) -> Prepare<'a, S> {
+        let name = self.make_stmt_name();
+        debug!("preparing query with name `{}`: {}", name, query);
+        let mut request = Request::new(self, true);
+        request.send(&[Parse {
+                           name: &name,
+                           query: query,
+                           param_types: &[],
+                       },
+                       Describe {
+                           variant: b'S',
+                           name: &name,
+                       },
+                       Sync]);
+        Prepare {
+            request: request,
+            name: name,
+            param_oids: vec![],
+            columns: vec![],
+            described: false,
+            lookups: vec![],
+            looking_up: false,
+            row: None,
+            fetched: HashMap::new(),
+        }
+    }
+
+    /// Executes a prepared statement, returning the number of rows modified.
+    ///
+    /// If the statement does not modify any rows (e.g. SELECT), 0 is
+    /// returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    pub fn execute<'a>(&'a mut self, stmt: &AsyncStatement, params: &[&ToSql]) -> Execute<'a, S> {
+        let mut request = Request::new(self, true);
+        request.bind(stmt, params);
+        Execute {
+            request: request,
+            count: 0,
+        }
+    }
+
+    /// Executes a prepared statement, returning the resulting rows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    pub fn query<'a>(&'a mut self, stmt: &AsyncStatement, params: &[&ToSql]) -> Query<'a, S> {
+        let mut request = Request::new(self, true);
+        request.bind(stmt, params);
+        Query {
+            request: request,
+            info: stmt.info.clone(),
+            rows: vec![],
+        }
+    }
+
+    /// Executes a sequence of SQL statements using the simple query
+    /// protocol.
+    ///
+    /// Statements should be separated by semicolons. If an error occurs,
+    /// execution of the sequence will stop at that point. This is intended
+    /// for use when, for example, initializing a database schema.
+    pub fn batch_execute<'a>(&'a mut self, query: &str) -> SimpleQuery<'a, S> {
+        debug!("executing query: {}", query);
+        let mut request = Request::new(self, false);
+        request.send(&[FrontendMessage::Query { query: query }]);
+        SimpleQuery { request: request }
+    }
+
+    /// Removes a prepared statement from the session.
+    pub fn close<'a>(&'a mut self, stmt: AsyncStatement) -> SimpleQuery<'a, S> {
+        let mut request = Request::new(self, true);
+        request.send(&[Close {
+                           variant: b'S',
+                           name: &stmt.info.name,
+                       },
+                       Sync]);
+        SimpleQuery { request: request }
+    }
+
+    /// Begins a new transaction.
+    ///
+    /// The future resolves to an `AsyncTransaction` which dereferences to
+    /// this connection. If this is called on an `AsyncTransaction`, a nested
+    /// transaction is started with a savepoint.
+    pub fn transaction<'a>(&'a mut self) -> Begin<'a, S> {
+        let query = if self.trans_depth == 0 {
+            "BEGIN"
+        } else {
+            "SAVEPOINT sp"
+        };
+        Begin { query: Some(self.batch_execute(query)) }
+    }
+
+    /// Executes a `COPY FROM STDIN` statement, reading the data to send to
+    /// the server from `reader` and returning the number of rows copied.
+    ///
+    /// See `Statement::copy_in` for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    pub fn copy_in<'a, R>(&'a mut self,
+                          stmt: &AsyncStatement,
+                          params: &[&ToSql],
+                          reader: R)
+                          -> CopyIn<'a, S, R>
+        where R: AsyncRead + Unpin
+    {
+        let mut request = Request::new(self, true);
+        request.copy_in = true;
+        request.bind(stmt, params);
+        CopyIn {
+            request: request,
+            reader: reader,
+            buf: vec![0; 16 * 1024],
+            state: CopyInState::Starting,
+            count: 0,
+        }
+    }
+
+    /// Executes a `COPY TO STDOUT` statement, passing the data returned by
+    /// the server to `writer` and returning the number of rows copied.
+    ///
+    /// See `Statement::copy_out` for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    pub fn copy_out<'a, W>(&'a mut self,
+                           stmt: &AsyncStatement,
+                           params: &[&ToSql],
+                           writer: W)
+                           -> CopyOut<'a, S, W>
+        where W: AsyncWrite + Unpin
+    {
+        let mut request = Request::new(self, true);
+        request.bind(stmt, params);
+        CopyOut {
+            request: request,
+            writer: writer,
+            data: None,
+            copying: false,
+            count: 0,
+        }
+    }
+
+    /// Returns a stream of asynchronous notifications.
+    ///
+    /// Use the `LISTEN` command to register this connection for
+    /// notifications. The stream never ends; it waits for further
+    /// notifications once the pending ones have been returned.
+    pub fn notifications<'a>(&'a mut self) -> Notifications<'a, S> {
+        Notifications { conn: self }
+    }
+
+    /// Terminates the session and closes the stream.
+    pub fn finish(mut self) -> Finish<S> {
+        let result = self.send(&[Terminate]);
+        Finish {
+            conn: self,
+            error: result.err(),
+        }
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.stream).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => {
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                          "failed to write to the stream")));
+                }
+                Poll::Ready(Ok(n)) => {
+                    self.write_buf.drain(..n);
+                }
+                Poll::Ready(Err(err)) => {
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(err));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        match Pin::new(&mut self.stream).poll_flush(cx) {
+            Poll::Ready(Err(err)) => {
+                self.desynchronized = true;
+                Poll::Ready(Err(err))
+            }
+            poll => poll,
+        }
+    }
+
+    fn poll_raw_message(&mut self, cx: &mut Context) -> Poll<io::Result<BackendMessage>> {
+        try_poll!(self.poll_write_buf(cx));
+
+        loop {
+            match message::parse_message(&self.read_buf[self.read_pos..]) {
+                Ok(Some((message, len))) => {
+                    self.read_pos += len;
+                    return Poll::Ready(Ok(message));
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(err));
+                }
+            }
+
+            self.read_buf.drain(..self.read_pos);
+            self.read_pos = 0;
+            let len = self.read_buf.len();
+            self.read_buf.resize(len + 8 * 1024, 0);
+            let poll = Pin::new(&mut self.stream).poll_read(cx, &mut self.read_buf[len..]);
+            match poll {
+                Poll::Ready(Ok(0)) => {
+                    self.read_buf.truncate(len);
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                          "unexpected EOF")));
+                }
+                Poll::Ready(Ok(n)) => self.read_buf.truncate(len + n),
+                Poll::Ready(Err(err)) => {
+                    self.read_buf.truncate(len);
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(err));
+                }
+                Poll::Pending => {
+                    self.read_buf.truncate(len);
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+
+    // Like poll_raw_message, but handles notices and parameter changes, and
+    // discards the responses to abandoned requests.
+    fn poll_message(&mut self, cx: &mut Context) -> Poll<io::Result<BackendMessage>> {
+        loop {
+            match try_poll!(self.poll_raw_message(cx)) {
+                NoticeResponse { fields } => {
+                    if let Ok(err) = DbError::new_raw(fields) {
+                        self.notice_handler.handle_notice(err);
+                    }
+                }
+                ParameterStatus { parameter, value } => {
+                    Arc::make_mut(&mut self.parameters).insert(parameter, value);
+                }
+                message @ NotificationResponse { .. } => return Poll::Ready(Ok(message)),
+                ReadyForQuery { .. } if self.abandoned > 0 => self.abandoned -= 1,
+                CopyInResponse { .. } if self.abandoned > 0 => {
+                    // There's no way to tell whether the abandoned request
+                    // still owes the server a Sync
+                    self.desynchronized = true;
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other,
+                                                          "an abandoned COPY FROM STDIN \
+                                                           statement cannot be recovered")));
+                }
+                message => {
+                    if self.abandoned == 0 {
+                        return Poll::Ready(Ok(message));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A future which resolves to a new `AsyncConnection`.
+pub struct Connect<S> {
+    handshake: Option<Handshake<S>>,
+    error: Option<ConnectError>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Connect<S> {
+    type Output = result::Result<AsyncConnection<S>, ConnectError>;
+
+    fn poll(self: Pin<&mut Self>,
+            cx: &mut Context)
+            -> Poll<result::Result<AsyncConnection<S>, ConnectError>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+
+        loop {
+            let done = {
+                let handshake = this.handshake.as_mut().expect("`Connect` polled after completion");
+                let message = try_poll!(handshake.conn.poll_message(cx));
+                try_result!(handshake.handle(message))
+            };
+            if done {
+                let handshake = this.handshake.take().unwrap();
+                return Poll::Ready(Ok(handshake.conn));
+            }
+        }
+    }
+}
+
+struct Handshake<S> {
+    conn: AsyncConnection<S>,
+    params: ConnectParams,
+    user: UserInfo,
+    state: HandshakeState,
+}
+
+enum HandshakeState {
+    Authenticating(Authenticator),
+    Startup,
+}
+
+impl<S> Handshake<S> {
+    // Returns true once the connection is ready for queries.
+    fn handle(&mut self, message: BackendMessage) -> result::Result<bool, ConnectError> {
+        match (mem::replace(&mut self.state, HandshakeState::Startup), message) {
+            (state, NotificationResponse { pid, channel, payload }) => {
+                self.conn.notifications.push_back(Notification {
+                    pid: pid,
+                    channel: channel,
+                    payload: payload,
+                });
+                self.state = state;
+            }
+            (_, ErrorResponse { fields }) => return DbError::new_connect(fields),
+            (HandshakeState::Authenticating(mut auth), message) => {
+                // The stream's channel binding data isn't available.
+                let response = try!(auth.handle(message, &self.params, &self.user, || None));
+                if let Some(response) = response {
+                    try!(self.conn.send(&[response]));
+                }
+                if !auth.is_done() {
+                    self.state = HandshakeState::Authenticating(auth);
+                }
+            }
+            (HandshakeState::Startup, BackendKeyData { process_id, secret_key }) => {
+                self.conn.cancel_data.process_id = process_id;
+                self.conn.cancel_data.secret_key = secret_key;
+            }
+            (HandshakeState::Startup, ReadyForQuery { .. }) => return Ok(true),
+            _ => return Err(ConnectError::Io(bad_response())),
+        }
+        Ok(false)
+    }
+}
+
+// An operation which has been sent to the server.
+struct Request<'a, S: 'a> {
+    conn: Option<&'a mut AsyncConnection<S>>,
+    // The number of ReadyForQuery messages which have yet to be received
+    pending: u32,
+    // Whether the extended query protocol is in use
+    extended: bool,
+    // Whether the caller handles CopyInResponse
+    copy_in: bool,
+    // Whether the server is waiting for COPY data
+    in_copy_in: bool,
+    error: Option<Error>,
+}
+
+impl<'a, S> Drop for Request<'a, S> {
+    fn drop(&mut self) {
+        if let Some(ref mut conn) = self.conn {
+            if self.in_copy_in {
+                match conn.send(&[CopyFail { message: "COPY was abandoned" }, Sync]) {
+                    Ok(()) => self.pending += 1,
+                    Err(_) => conn.desynchronized = true,
+                }
+            }
+            conn.abandoned += self.pending;
+        }
+    }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Request<'a, S> {
+    fn new(conn: &'a mut AsyncConnection<S>, extended: bool) -> Request<'a, S> {
+        let error = if conn.desynchronized {
+            Some(Error::Io(desynchronized()))
+        } else {
+            None
+        };
+
+        Request {
+            conn: Some(conn),
+            pending: 0,
+            extended: extended,
+            copy_in: false,
+            in_copy_in: false,
+            error: error,
+        }
+    }
+
+    fn conn(&mut self) -> &mut AsyncConnection<S> {
+        self.conn.as_mut().expect("request used after completion")
+    }
+
+    fn into_conn(mut self) -> &'a mut AsyncConnection<S> {
+        self.conn.take().expect("request used after completion")
+    }
+
+    fn fail(&mut self, err: Error) {
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+    }
+
+    fn unexpected(&mut self) {
+        self.fail(Error::Io(bad_response()));
+    }
+
+    fn send(&mut self, messages: &[FrontendMessage]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let ends = messages.iter()
+                           .filter(|m| {
+                               match **m {
+                                   Sync | FrontendMessage::Query { .. } => true,
+                                   _ => false,
+                               }
+                           })
+                           .count() as u32;
+        match self.conn().send(messages) {
+            Ok(()) => self.pending += ends,
+            Err(err) => {
+                self.conn().desynchronized = true;
+                self.fail(Error::Io(err));
+            }
+        }
+    }
+
+    fn bind(&mut self, stmt: &AsyncStatement, params: &[&ToSql]) {
+        let values = match stmt.info.bind_values(params, &self.conn().parameters) {
+            Ok(values) => values,
+            Err(err) => return self.fail(err),
+        };
+        self.send(&[Bind {
+                        portal: "",
+                        statement: &stmt.info.name,
+                        formats: &[1],
+                        values: &values,
+                        result_formats: &[1],
+                    },
+                    FrontendMessage::Execute {
+                        portal: "",
+                        max_rows: 0,
+                    },
+                    Sync]);
+    }
+
+    // Returns the next message of the response, or `None` once it is
+    // complete. Messages following an error are discarded.
+    fn poll_message(&mut self, cx: &mut Context) -> Poll<Result<Option<BackendMessage>>> {
+        loop {
+            if self.pending == 0 && !self.in_copy_in {
+                return Poll::Ready(match self.error.take() {
+                    Some(err) => Err(err),
+                    None => Ok(None),
+                });
+            }
+
+            match try_poll!(self.conn().poll_message(cx)) {
+                ReadyForQuery { .. } => {
+                    if self.pending == 0 {
+                        self.conn().desynchronized = true;
+                        return Poll::Ready(Err(Error::Io(bad_response())));
+                    }
+                    self.pending -= 1;
+                }
+                NotificationResponse { pid, channel, payload } => {
+                    self.conn().notifications.push_back(Notification {
+                        pid: pid,
+                        channel: channel,
+                        payload: payload,
+                    });
+                }
+                ErrorResponse { fields } => {
+                    match DbError::new_raw(fields) {
+                        Ok(err) => self.fail(Error::Db(Box::new(err))),
+                        Err(()) => self.unexpected(),
+                    }
+                }
+                CopyInResponse { .. } if !self.copy_in => {
+                    let fail = CopyFail { message: "COPY queries cannot be directly executed" };
+                    let result = if self.extended {
+                        self.conn().send(&[fail, Sync])
+                    } else {
+                        self.conn().send(&[fail])
+                    };
+                    if let Err(err) = result {
+                        self.conn().desynchronized = true;
+                        return Poll::Ready(Err(Error::Io(err)));
+                    }
+                }
+                message => {
+                    if self.error.is_none() {
+                        return Poll::Ready(Ok(Some(message)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A future which resolves to a new `AsyncStatement`.
+pub struct Prepare<'a, S: 'a> {
+    request: Request<'a, S>,
+    name: String,
+    param_oids: Vec<Oid>,
+    columns: Vec<RowDescriptionEntry>,
+    described: bool,
+    // Types which need to be looked up. A type's element or subtype is
+    // pushed on top of it.
+    lookups: Vec<Oid>,
+    looking_up: bool,
+    row: Option<Vec<Option<Vec<u8>>>>,
+    fetched: HashMap<Oid, TypeInfo>,
+}
+
+struct TypeInfo {
+    name: String,
+    elem_oid: Oid,
+    rngsubtype: Option<Oid>,
+    schema: String,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Future for Prepare<'a, S> {
+    type Output = Result<AsyncStatement>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<AsyncStatement>> {
+        let this = self.get_mut();
+        loop {
+            let message = match this.request.poll_message(cx) {
+                Poll::Ready(Ok(message)) => message,
+                Poll::Ready(Err(Error::Db(ref err))) if this.looking_up &&
+                                                        err.code == SqlState::UndefinedTable &&
+                                                        this.request.conn().typeinfo_query ==
+                                                        TYPEINFO_SQL => {
+                    this.request.conn().typeinfo_query = TYPEINFO_FALLBACK_SQL;
+                    this.looking_up = false;
+                    None
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match message {
+                Some(ParseComplete) | Some(BindComplete) | Some(NoData) => {}
+                Some(CommandComplete { .. }) => {}
+                Some(ParameterDescription { types }) => this.param_oids = types,
+                Some(RowDescription { descriptions }) => this.columns = descriptions,
+                Some(DataRow { row }) => this.row = Some(row),
+                Some(_) => this.request.unexpected(),
+                None => {
+                    if let Some(stmt) = try_result!(this.step()) {
+                        return Poll::Ready(Ok(stmt));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Prepare<'a, S> {
+    // Called after each response completes. Either sends the next type
+    // lookup or returns the finished statement.
+    fn step(&mut self) -> Result<Option<AsyncStatement>> {
+        if !self.described {
+            self.described = true;
+            let oids = self.param_oids.iter().chain(self.columns.iter().map(|c| &c.type_oid));
+            self.lookups.extend(oids.rev());
+        }
+
+        if self.looking_up {
+            self.looking_up = false;
+            let oid = *self.lookups.last().unwrap();
+            let row = try!(self.row.take().ok_or(Error::Io(bad_response())));
+            let info = try!(read_type_info(&row, &self.request.conn().parameters));
+            self.fetched.insert(oid, info);
+        }
+
+        while let Some(&oid) = self.lookups.last() {
+            if self.request.conn().cached_type(oid).is_some() {
+                self.lookups.pop();
+                continue;
+            }
+
+            let (elem_oid, rngsubtype) = match self.fetched.get(&oid) {
+                Some(info) => (info.elem_oid, info.rngsubtype),
+                None => {
+                    try!(self.send_lookup(oid));
+                    return Ok(None);
+                }
+            };
+
+            let sub_oid = if elem_oid != 0 {
+                Some(elem_oid)
+            } else {
+                rngsubtype
+            };
+            let kind = match sub_oid {
+                Some(sub_oid) => {
+                    match self.request.conn().cached_type(sub_oid) {
+                        Some(ty) if elem_oid != 0 => Kind::Array(ty),
+                        Some(ty) => Kind::Range(ty),
+                        None => {
+                            self.lookups.push(sub_oid);
+                            continue;
+                        }
+                    }
+                }
+                None => Kind::Simple,
+            };
+
+            let info = self.fetched.remove(&oid).unwrap();
+            let type_ = Other::new(info.name, oid, kind, info.schema);
+            self.request.conn().unknown_types.insert(oid, type_);
+            self.lookups.pop();
+        }
+
+        let conn = self.request.conn();
+        let param_types = self.param_oids
+                              .iter()
+                              .map(|&oid| conn.cached_type(oid).unwrap())
+                              .collect();
+        let columns = self.columns
+                          .drain(..)
+                          .map(|c| Column::new(c.name, conn.cached_type(c.type_oid).unwrap()))
+                          .collect();
+        let info = StatementInfo {
+            name: mem::replace(&mut self.name, String::new()),
+            param_types: param_types,
+            columns: columns,
+        };
+        Ok(Some(AsyncStatement { info: Arc::new(info) }))
+    }
+
+    fn send_lookup(&mut self, oid: Oid) -> Result<()> {
+        let mut buf = vec![];
+        {
+            let conn = self.request.conn();
+            try!(oid.to_sql_checked(&Type::Oid, &mut buf, &SessionInfo::new(&conn.parameters)));
+        }
+        let query = self.request.conn().typeinfo_query;
+        self.request.send(&[Parse {
+                                name: "",
+                                query: query,
+                                param_types: &[],
+                            },
+                            Bind {
+                                portal: "",
+                                statement: "",
+                                formats: &[1],
+                                values: &[Some(buf)],
+                                result_formats: &[1],
+                            },
+                            FrontendMessage::Execute {
+                                portal: "",
+                                max_rows: 0,
+                            },
+                            Sync]);
+        self.looking_up = true;
+        Ok(())
+    }
+}
+
+fn read_type_info(row: &[Option<Vec<u8>>],
+                  parameters: &HashMap<String, String>)
+                  -> Result<TypeInfo> {
+    if row.len() != 4 || row[0].is_none() || row[1].is_none() || row[3].is_none() {
+        return Err(Error::Io(bad_response()));
+    }
+
+    let ctx = SessionInfo::new(parameters);
+    let name = try!(String::from_sql(&Type::Name, &mut &**row[0].as_ref().unwrap(), &ctx));
+    let elem_oid = try!(Oid::from_sql(&Type::Oid, &mut &**row[1].as_ref().unwrap(), &ctx));
+    let rngsubtype = match row[2] {
+        Some(ref data) => try!(Option::<Oid>::from_sql(&Type::Oid, &mut &**data, &ctx)),
+        None => try!(Option::<Oid>::from_sql_null(&Type::Oid, &ctx)),
+    };
+    let schema = try!(String::from_sql(&Type::Name, &mut &**row[3].as_ref().unwrap(), &ctx));
+    Ok(TypeInfo {
+        name: name,
+        elem_oid: elem_oid,
+        rngsubtype: rngsubtype,
+        schema: schema,
+    })
+}
+
+/// A future which resolves to the number of rows modified by a statement.
+pub struct Execute<'a, S: 'a> {
+    request: Request<'a, S>,
+    count: u64,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Future for Execute<'a, S> {
+    type Output = Result<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<u64>> {
+        let this = self.get_mut();
+        loop {
+            match try_poll!(this.request.poll_message(cx)) {
+                Some(BindComplete) | Some(DataRow { .. }) | Some(EmptyQueryResponse) => {}
+                Some(CopyOutResponse { .. }) | Some(BCopyData { .. }) | Some(BCopyDone) => {}
+                Some(CommandComplete { tag }) => this.count = util::parse_update_count(tag),
+                Some(_) => this.request.unexpected(),
+                None => return Poll::Ready(Ok(this.count)),
+            }
+        }
+    }
+}
+
+/// A future which resolves to the rows returned by a statement.
+pub struct Query<'a, S: 'a> {
+    request: Request<'a, S>,
+    info: Arc<StatementInfo>,
+    rows: Vec<AsyncRow>,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Future for Query<'a, S> {
+    type Output = Result<Vec<AsyncRow>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Vec<AsyncRow>>> {
+        let this = self.get_mut();
+        loop {
+            match try_poll!(this.request.poll_message(cx)) {
+                Some(BindComplete) | Some(CommandComplete { .. }) | Some(EmptyQueryResponse) => {}
+                Some(DataRow { row }) => {
+                    let row = AsyncRow {
+                        info: this.info.clone(),
+                        parameters: this.request.conn().parameters.clone(),
+                        data: row,
+                    };
+                    this.rows.push(row);
+                }
+                Some(_) => this.request.unexpected(),
+                None => return Poll::Ready(Ok(mem::replace(&mut this.rows, vec![]))),
+            }
+        }
+    }
+}
+
+/// A future which resolves once a request without a result has completed.
+pub struct SimpleQuery<'a, S: 'a> {
+    request: Request<'a, S>,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Future for SimpleQuery<'a, S> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.get_mut().poll_inner(cx)
+    }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> SimpleQuery<'a, S> {
+    fn poll_inner(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        // Any rows or command tags are discarded
+        while let Some(_) = try_poll!(self.request.poll_message(cx)) {}
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A future which resolves to a new `AsyncTransaction`.
+pub struct Begin<'a, S: 'a> {
+    query: Option<SimpleQuery<'a, S>>,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Future for Begin<'a, S> {
+    type Output = Result<AsyncTransaction<'a, S>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<AsyncTransaction<'a, S>>> {
+        let this = self.get_mut();
+        try_poll!(this.query.as_mut().expect("`Begin` polled after completion").poll_inner(cx));
+        let conn = this.query.take().unwrap().request.into_conn();
+        conn.trans_depth += 1;
+        let depth = conn.trans_depth;
+        Poll::Ready(Ok(AsyncTransaction {
+            conn: Some(conn),
+            depth: depth,
+        }))
+    }
+}
+
+/// A transaction on an `AsyncConnection`.
+///
+/// The transaction dereferences to its connection, which should be used to
+/// execute statements for the duration of the transaction. If it is dropped
+/// without calling `commit`, the transaction will be rolled back before the
+/// next operation on the connection.
+pub struct AsyncTransaction<'a, S: 'a> {
+    conn: Option<&'a mut AsyncConnection<S>>,
+    depth: u32,
+}
+
+impl<'a, S> fmt::Debug for AsyncTransaction<'a, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncTransaction")
+           .field("depth", &self.depth)
+           .finish()
+    }
+}
+
+impl<'a, S> Drop for AsyncTransaction<'a, S> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            debug_assert!(conn.trans_depth == self.depth);
+            conn.trans_depth -= 1;
+            let query = if self.depth == 1 {
+                "ROLLBACK"
+            } else {
+                "ROLLBACK TO sp"
+            };
+            match conn.send(&[FrontendMessage::Query { query: query }]) {
+                Ok(()) => conn.abandoned += 1,
+                Err(_) => conn.desynchronized = true,
+            }
+        }
+    }
+}
+
+impl<'a, S> Deref for AsyncTransaction<'a, S> {
+    type Target = AsyncConnection<S>;
+
+    fn deref(&self) -> &AsyncConnection<S> {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl<'a, S> DerefMut for AsyncTransaction<'a, S> {
+    fn deref_mut(&mut self) -> &mut AsyncConnection<S> {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> AsyncTransaction<'a, S> {
+    /// Commits the transaction.
+    pub fn commit(self) -> SimpleQuery<'a, S> {
+        let query = if self.depth == 1 {
+            "COMMIT"
+        } else {
+            "RELEASE sp"
+        };
+        self.finish(query)
+    }
+
+    /// Rolls back the transaction.
+    pub fn rollback(self) -> SimpleQuery<'a, S> {
+        let query = if self.depth == 1 {
+            "ROLLBACK"
+        } else {
+            "ROLLBACK TO sp"
+        };
+        self.finish(query)
+    }
+
+    fn finish(mut self, query: &str) -> SimpleQuery<'a, S> {
+        let conn = self.conn.take().unwrap();
+        debug_assert!(conn.trans_depth == self.depth);
+        conn.trans_depth -= 1;
+        conn.batch_execute(query)
+    }
+}
+
+/// A future which resolves to the number of rows copied into the database.
+pub struct CopyIn<'a, S: 'a, R> {
+    request: Request<'a, S>,
+    reader: R,
+    buf: Vec<u8>,
+    state: CopyInState,
+    count: u64,
+}
+
+enum CopyInState {
+    Starting,
+    Copying,
+    Finishing,
+}
+
+impl<'a, S, R> Future for CopyIn<'a, S, R>
+    where S: AsyncRead + AsyncWrite + Unpin,
+          R: AsyncRead + Unpin
+{
+    type Output = Result<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<u64>> {
+        let this = self.get_mut();
+        loop {
+            match this.state {
+                CopyInState::Starting => {
+                    match try_poll!(this.request.poll_message(cx)) {
+                        Some(BindComplete) => {}
+                        Some(CopyInResponse { .. }) => {
+                            // The server ignores the Sync sent after Execute while copying
+                            this.request.pending -= 1;
+                            this.request.in_copy_in = true;
+                            this.state = CopyInState::Copying;
+                        }
+                        Some(_) => this.request.unexpected(),
+                        None => {
+                            return Poll::Ready(Err(Error::Io(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "called `copy_in` on a non-`COPY FROM STDIN` statement"))));
+                        }
+                    }
+                }
+                CopyInState::Copying => {
+                    // Only read more data once the previous chunk has been sent
+                    try_poll!(this.request.conn().poll_write_buf(cx));
+                    match Pin::new(&mut this.reader).poll_read(cx, &mut this.buf) {
+                        Poll::Ready(Ok(0)) => {
+                            this.request.in_copy_in = false;
+                            this.request.send(&[CopyDone, Sync]);
+                            this.state = CopyInState::Finishing;
+                        }
+                        Poll::Ready(Ok(n)) => {
+                            this.request.send(&[CopyData { data: &this.buf[..n] }]);
+                        }
+                        Poll::Ready(Err(ref err)) if err.kind() == io::ErrorKind::Interrupted => {}
+                        Poll::Ready(Err(err)) => {
+                            this.request.in_copy_in = false;
+                            this.request.send(&[CopyFail { message: "" }, Sync]);
+                            this.request.fail(Error::Io(err));
+                            this.state = CopyInState::Finishing;
+                        }
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }
+                CopyInState::Finishing => {
+                    match try_poll!(this.request.poll_message(cx)) {
+                        Some(CommandComplete { tag }) => this.count = util::parse_update_count(tag),
+                        Some(_) => this.request.unexpected(),
+                        None => return Poll::Ready(Ok(this.count)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A future which resolves to the number of rows copied out of the database.
+pub struct CopyOut<'a, S: 'a, W> {
+    request: Request<'a, S>,
+    writer: W,
+    data: Option<(Vec<u8>, usize)>,
+    copying: bool,
+    count: u64,
+}
+
+impl<'a, S, W> Future for CopyOut<'a, S, W>
+    where S: AsyncRead + AsyncWrite + Unpin,
+          W: AsyncWrite + Unpin
+{
+    type Output = Result<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<u64>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((data, mut pos)) = this.data.take() {
+                while pos < data.len() {
+                    match Pin::new(&mut this.writer).poll_write(cx, &data[pos..]) {
+                        Poll::Ready(Ok(0)) => {
+                            let err = io::Error::new(io::ErrorKind::WriteZero,
+                                                     "failed to write COPY data");
+                            this.request.fail(Error::Io(err));
+                            break;
+                        }
+                        Poll::Ready(Ok(n)) => pos += n,
+                        Poll::Ready(Err(err)) => {
+                            // Keep reading so the connection stays synchronized
+                            this.request.fail(Error::Io(err));
+                            break;
+                        }
+                        Poll::Pending => {
+                            this.data = Some((data, pos));
+                            return Poll::Pending;
+                        }
+                    }
+                }
+            }
+
+            match try_poll!(this.request.poll_message(cx)) {
+                Some(BindComplete) | Some(BCopyDone) => {}
+                Some(CopyOutResponse { .. }) => this.copying = true,
+                Some(BCopyData { data }) => this.data = Some((data, 0)),
+                Some(CommandComplete { tag }) => this.count = util::parse_update_count(tag),
+                Some(_) => this.request.unexpected(),
+                None => {
+                    if !this.copying {
+                        return Poll::Ready(Err(Error::Io(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "called `copy_out` on a non-`COPY TO STDOUT` statement"))));
+                    }
+                    try_poll!(Pin::new(&mut this.writer).poll_flush(cx));
+                    return Poll::Ready(Ok(this.count));
+                }
+            }
+        }
+    }
+}
+
+/// A stream of asynchronous notifications received by an
+/// `AsyncConnection`.
+pub struct Notifications<'a, S: 'a> {
+    conn: &'a mut AsyncConnection<S>,
+}
+
+impl<'a, S> fmt::Debug for Notifications<'a, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Notifications")
+           .field("pending", &self.conn.notifications.len())
+           .finish()
+    }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Stream for Notifications<'a, S> {
+    type Item = Result<Notification>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Notification>>> {
+        let conn = &mut *self.get_mut().conn;
+        if let Some(notification) = conn.notifications.pop_front() {
+            return Poll::Ready(Some(Ok(notification)));
+        }
+
+        if conn.desynchronized {
+            return Poll::Ready(Some(Err(Error::Io(desynchronized()))));
+        }
+
+        match conn.poll_message(cx) {
+            Poll::Ready(Ok(NotificationResponse { pid, channel, payload })) => {
+                Poll::Ready(Some(Ok(Notification {
+                    pid: pid,
+                    channel: channel,
+                    payload: payload,
+                })))
+            }
+            Poll::Ready(Ok(_)) => {
+                conn.desynchronized = true;
+                Poll::Ready(Some(Err(Error::Io(bad_response()))))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(Error::Io(err)))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A future which resolves once the session has been terminated.
+pub struct Finish<S> {
+    conn: AsyncConnection<S>,
+    error: Option<io::Error>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Finish<S> {
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(Error::Io(err)));
+        }
+
+        try_poll!(this.conn.poll_write_buf(cx));
+        try_poll!(Pin::new(&mut this.conn.stream).poll_close(cx));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A prepared statement for use with an `AsyncConnection`.
+///
+/// Unlike `Statement`, an `AsyncStatement` is not tied to the lifetime of its
+/// connection and is not closed when dropped.
+#[derive(Clone)]
+pub struct AsyncStatement {
+    info: Arc<StatementInfo>,
+}
+
+impl fmt::Debug for AsyncStatement {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncStatement")
+           .field("name", &self.info.name)
+           .field("parameter_types", &self.info.param_types)
+           .field("columns", &self.info.columns)
+           .finish()
+    }
+}
+
+impl AsyncStatement {
+    /// Returns a slice containing the expected parameter types.
+    pub fn param_types(&self) -> &[Type] {
+        &self.info.param_types
+    }
+
+    /// Returns a slice describing the columns of the result of the query.
+    pub fn columns(&self) -> &[Column] {
+        &self.info.columns
+    }
+}
+
+/// A single result row of a query executed on an `AsyncConnection`.
+pub struct AsyncRow {
+    info: Arc<StatementInfo>,
+    parameters: Arc<HashMap<String, String>>,
+    data: Vec<Option<Vec<u8>>>,
+}
+
+impl fmt::Debug for AsyncRow {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncRow")
+           .field("columns", &self.info.columns)
+           .finish()
+    }
+}
+
+impl AsyncRow {
+    /// Returns the number of values in the row.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Determines if the row contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns a slice describing the columns of the `AsyncRow`.
+    pub fn columns(&self) -> &[Column] {
+        &self.info.columns
+    }
+
+    /// Retrieves the contents of a field of the row.
+    ///
+    /// Like `Row::get`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index does not reference a column or the return type is
+    /// not compatible with the Postgres type.
+    pub fn get<I, T>(&self, idx: I) -> T
+        where I: AsyncRowIndex + fmt::Debug,
+              T: FromSql
+    {
+        match self.get_opt(&idx) {
+            Some(Ok(ok)) => ok,
+            Some(Err(err)) => panic!("error retrieving column {:?}: {:?}", idx, err),
+            None => panic!("no such column {:?}", idx),
+        }
+    }
+
+    /// Retrieves the contents of a field of the row.
+    ///
+    /// Like `Row::get_opt`.
+    pub fn get_opt<I, T>(&self, idx: I) -> Option<Result<T>>
+        where I: AsyncRowIndex,
+              T: FromSql
+    {
+        let idx = match idx.idx(&self.info.columns) {
+            Some(idx) => idx,
+            None => return None,
+        };
+
+        let ty = self.info.columns[idx].type_();
+        if !<T as FromSql>::accepts(ty) {
+            return Some(Err(Error::Conversion(Box::new(WrongType::new(ty.clone())))));
+        }
+        let ctx = SessionInfo::new(&self.parameters);
+        let value = match self.data[idx] {
+            Some(ref data) => FromSql::from_sql(ty, &mut &**data, &ctx),
+            None => FromSql::from_sql_null(ty, &ctx),
+        };
+        Some(value)
+    }
+
+    /// Retrieves the specified field as a raw buffer of Postgres data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index does not reference a column.
+    pub fn get_bytes<I>(&self, idx: I) -> Option<&[u8]>
+        where I: AsyncRowIndex + fmt::Debug
+    {
+        match idx.idx(&self.info.columns) {
+            Some(idx) => self.data[idx].as_ref().map(|e| &**e),
+            None => panic!("invalid index {:?}", idx),
+        }
+    }
+}
+
+/// A trait implemented by types that can index into columns of an
+/// `AsyncRow`.
+pub trait AsyncRowIndex {
+    /// Returns the index of the appropriate column, or `None` if no such
+    /// column exists.
+    fn idx(&self, columns: &[Column]) -> Option<usize>;
+}
+
+impl<'a, T: AsyncRowIndex + ?Sized> AsyncRowIndex for &'a T {
+    fn idx(&self, columns: &[Column]) -> Option<usize> {
+        T::idx(*self, columns)
+    }
+}
+
+impl AsyncRowIndex for usize {
+    fn idx(&self, columns: &[Column]) -> Option<usize> {
+        if *self >= columns.len() {
+            None
+        } else {
+            Some(*self)
+        }
+    }
+}
+
+impl AsyncRowIndex for str {
+    fn idx(&self, columns: &[Column]) -> Option<usize> {
+        util::column_index(columns, self)
+    }
+}
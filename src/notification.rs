@@ -3,7 +3,7 @@
 use std::fmt;
 use std::time::Duration;
 
-use {desynchronized, Result, Connection, NotificationsNew};
+use {desynchronized, streaming, Result, Connection, NotificationsNew};
 use message::BackendMessage::NotificationResponse;
 use error::Error;
 
@@ -104,6 +104,10 @@ impl<'a> Iterator for Iter<'a> {
             return Some(Err(Error::Io(desynchronized())));
         }
 
+        if conn.streaming {
+            return Some(Err(Error::Io(streaming())));
+        }
+
         match conn.read_message_with_notification_nonblocking() {
             Ok(Some(NotificationResponse { pid, channel, payload })) => {
                 Some(Ok(Notification {
@@ -142,6 +146,10 @@ impl<'a> Iterator for BlockingIter<'a> {
             return Some(Err(Error::Io(desynchronized())));
         }
 
+        if conn.streaming {
+            return Some(Err(Error::Io(streaming())));
+        }
+
         match conn.read_message_with_notification() {
             Ok(NotificationResponse { pid, channel, payload }) => {
                 Some(Ok(Notification {
@@ -181,6 +189,10 @@ impl<'a> Iterator for TimeoutIter<'a> {
             return Some(Err(Error::Io(desynchronized())));
         }
 
+        if conn.streaming {
+            return Some(Err(Error::Io(streaming())));
+        }
+
         match conn.read_message_with_notification_timeout(self.timeout) {
             Ok(Some(NotificationResponse { pid, channel, payload })) => {
                 Some(Ok(Notification {
@@ -0,0 +1,209 @@
+//! Password file support.
+//!
+//! See the [Postgres documentation](http://www.postgresql.org/docs/current/static/libpq-pgpass.html)
+//! for details on the file format.
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use {ConnectParams, ConnectTarget};
+use priv_io::DEFAULT_PORT;
+
+/// Looks up the password for the specified connection parameters and user in
+/// the password file.
+///
+/// The file is located via the `PGPASSFILE` environment variable, falling
+/// back to `~/.pgpass` (`%APPDATA%\postgresql\pgpass.conf` on Windows).
+/// `None` is returned if the file does not exist, has insecure permissions,
+/// or contains no matching entry.
+pub fn find_password(params: &ConnectParams, user: &str) -> Option<String> {
+    let path = match password_file() {
+        Some(path) => path,
+        None => return None,
+    };
+
+    let file = match open(&path) {
+        Ok(Some(file)) => file,
+        Ok(None) => return None,
+        Err(e) => {
+            debug!("unable to read password file {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let host = match params.target {
+        ConnectTarget::Tcp(ref host) => host.clone(),
+        #[cfg(feature = "unix_socket")]
+        ConnectTarget::Unix(ref path) => path.to_string_lossy().into_owned(),
+    };
+    let port = params.port.unwrap_or(DEFAULT_PORT).to_string();
+    let database = params.database.as_ref().map(|d| &**d).unwrap_or(user);
+
+    for line in BufReader::new(file).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                debug!("error reading password file {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let fields = match split_line(&line) {
+            Some(fields) => fields,
+            None => continue,
+        };
+
+        let host_matches = fields[0].matches(&host) ||
+                           (is_unix_target(params) && fields[0].matches("localhost"));
+        if host_matches && fields[1].matches(&port) && fields[2].matches(database) &&
+           fields[3].matches(user) {
+            return Some(fields[4].value.clone());
+        }
+    }
+
+    None
+}
+
+fn password_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PGPASSFILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    default_password_file()
+}
+
+#[cfg(unix)]
+#[allow(deprecated)]
+fn default_password_file() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".pgpass"))
+}
+
+#[cfg(windows)]
+fn default_password_file() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| {
+        PathBuf::from(appdata).join("postgresql").join("pgpass.conf")
+    })
+}
+
+#[cfg(unix)]
+fn open(path: &Path) -> io::Result<Option<File>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let metadata = try!(file.metadata());
+    if !metadata.is_file() {
+        warn!("password file {} is not a plain file", path.display());
+        return Ok(None);
+    }
+
+    // Like libpq, refuse to use the file if it is accessible by anyone other
+    // than its owner.
+    if metadata.permissions().mode() & 0o077 != 0 {
+        warn!("password file {} has group or world access; permissions should be u=rw (0600) \
+               or less",
+              path.display());
+        return Ok(None);
+    }
+
+    Ok(Some(file))
+}
+
+#[cfg(windows)]
+fn open(path: &Path) -> io::Result<Option<File>> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(feature = "unix_socket")]
+fn is_unix_target(params: &ConnectParams) -> bool {
+    match params.target {
+        ConnectTarget::Unix(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(not(feature = "unix_socket"))]
+fn is_unix_target(_: &ConnectParams) -> bool {
+    false
+}
+
+struct Field {
+    value: String,
+    wildcard: bool,
+}
+
+impl Field {
+    fn matches(&self, s: &str) -> bool {
+        self.wildcard || self.value == s
+    }
+}
+
+// Fields are separated by `:`, and `\` escapes the following character. A
+// field consisting of an unescaped `*` matches anything. The password is the
+// remainder of the line and is never a wildcard.
+fn split_line(line: &str) -> Option<Vec<Field>> {
+    let mut fields = vec![];
+    let mut chars = line.chars();
+
+    while fields.len() < 4 {
+        let mut value = String::new();
+        let mut escaped = false;
+        let mut terminated = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    escaped = true;
+                    if let Some(c) = chars.next() {
+                        value.push(c);
+                    }
+                }
+                ':' => {
+                    terminated = true;
+                    break;
+                }
+                c => value.push(c),
+            }
+        }
+
+        if !terminated {
+            return None;
+        }
+
+        let wildcard = !escaped && value == "*";
+        fields.push(Field {
+            value: value,
+            wildcard: wildcard,
+        });
+    }
+
+    let mut password = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    password.push(c);
+                }
+            }
+            c => password.push(c),
+        }
+    }
+    fields.push(Field {
+        value: password,
+        wildcard: false,
+    });
+
+    Some(fields)
+}
@@ -0,0 +1,309 @@
+//! Pipelined execution of prepared statements.
+//!
+//! A `Pipeline` sends the Bind and Execute messages for many queued
+//! statements back-to-back and only then reads their results, so executing
+//! `n` statements costs a handful of round trips rather than `n`.
+//!
+//! Statements are split into groups by sync points. A group is ended by a
+//! call to `Pipeline::sync`, and the last group is ended implicitly when the
+//! pipeline is run. If a statement fails, the remaining statements in its
+//! group are skipped by the server and reported as
+//! `PipelineResult::Skipped`. Execution resumes with the next group.
+//!
+//! Outside of a transaction, the statements in a group are executed in a
+//! single implicit transaction which is committed at the end of the group. A
+//! failure therefore also rolls back the earlier statements of its group,
+//! even though their results have been reported as successful. Call `sync`
+//! after every statement to execute each one independently.
+//!
+//! Messages are written in chunks of about 32 KiB, and the results of each
+//! chunk are read before the next one is written. The server stops reading
+//! messages while the client is not reading its results, so this bounds the
+//! unread data to what the socket buffers can hold, and any number of
+//! statements can be queued.
+
+use std::fmt;
+use std::io;
+
+use {bad_response, Connection, DbErrorNew, InnerConnection, PipelineNew, Result, RowsNew,
+     StatementInternals};
+use error::{DbError, Error};
+use message::BackendMessage::*;
+use message::FrontendMessage::*;
+use rows::Rows;
+use stmt::Statement;
+use types::ToSql;
+use util;
+
+// The approximate number of bytes of messages written before the results of
+// the statements written so far are read. It is small enough to fit in the
+// socket buffers while the server is blocked sending results.
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// The result of a statement executed in a `Pipeline`.
+#[derive(Debug)]
+pub enum PipelineResult<'stmt> {
+    /// The number of rows modified by a statement queued with
+    /// `Pipeline::execute`.
+    Execute(u64),
+    /// The rows returned by a statement queued with `Pipeline::query`.
+    Query(Rows<'stmt>),
+    /// The error reported by the server for a failed statement.
+    Error(Box<DbError>),
+    /// The statement was not executed because an earlier statement in its
+    /// group failed.
+    Skipped,
+}
+
+struct Entry<'stmt> {
+    stmt: &'stmt Statement<'stmt>,
+    values: Vec<Option<Vec<u8>>>,
+    returns_rows: bool,
+    sync: bool,
+}
+
+/// A queue of prepared statement executions to be sent to the server
+/// together.
+pub struct Pipeline<'conn> {
+    conn: &'conn Connection,
+    entries: Vec<Entry<'conn>>,
+}
+
+impl<'a> fmt::Debug for Pipeline<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Pipeline")
+           .field("queued", &self.entries.len())
+           .finish()
+    }
+}
+
+impl<'conn> PipelineNew<'conn> for Pipeline<'conn> {
+    fn new(conn: &'conn Connection) -> Pipeline<'conn> {
+        Pipeline {
+            conn: conn,
+            entries: vec![],
+        }
+    }
+}
+
+impl<'conn> Pipeline<'conn> {
+    /// Queues an execution of a statement, the result of which will be the
+    /// number of rows modified.
+    ///
+    /// The parameters are converted immediately, and an error is returned if
+    /// that fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the statement is not associated with the same `Connection`
+    /// as this `Pipeline`, or if the number of parameters provided does not
+    /// match the number expected.
+    pub fn execute(&mut self, stmt: &'conn Statement<'conn>, params: &[&ToSql]) -> Result<()> {
+        self.push(stmt, params, false)
+    }
+
+    /// Queues an execution of a statement, the result of which will be the
+    /// rows returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the statement is not associated with the same `Connection`
+    /// as this `Pipeline`, or if the number of parameters provided does not
+    /// match the number expected.
+    pub fn query(&mut self, stmt: &'conn Statement<'conn>, params: &[&ToSql]) -> Result<()> {
+        self.push(stmt, params, true)
+    }
+
+    fn push(&mut self,
+            stmt: &'conn Statement<'conn>,
+            params: &[&ToSql],
+            returns_rows: bool)
+            -> Result<()> {
+        assert!(self.conn as *const _ == stmt.conn() as *const _,
+                "the `Statement` passed to `Pipeline` is not associated with its `Connection`");
+        let values = try!(stmt.bind_values(&self.conn.conn.borrow(), params));
+        self.entries.push(Entry {
+            stmt: stmt,
+            values: values,
+            returns_rows: returns_rows,
+            sync: false,
+        });
+        Ok(())
+    }
+
+    /// Ends the current group of statements.
+    ///
+    /// A failure of a statement in a later group will not cause the
+    /// statements queued so far to be skipped. This has no effect if no
+    /// statements have been queued since the last sync point.
+    pub fn sync(&mut self) {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.sync = true;
+        }
+    }
+
+    /// Returns the number of queued statements.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines if no statements have been queued.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Executes the queued statements, returning their results in the order
+    /// they were queued.
+    ///
+    /// Errors reported by the server for individual statements are returned
+    /// as `PipelineResult::Error`. An `Err` is only returned if communication
+    /// with the server failed, in which case the connection is no longer
+    /// usable.
+    ///
+    /// # Note
+    ///
+    /// `COPY ... FROM STDIN` statements cannot be pipelined, and executing
+    /// one will desynchronize the connection. The data returned by
+    /// `COPY ... TO STDOUT` statements is discarded.
+    pub fn run(mut self) -> Result<Vec<PipelineResult<'conn>>> {
+        let mut conn = self.conn.conn.borrow_mut();
+        check_desync!(conn);
+        match self.entries.last_mut() {
+            Some(entry) => entry.sync = true,
+            None => return Ok(vec![]),
+        }
+
+        let mut results = Vec::with_capacity(self.entries.len());
+        let mut failed = false;
+        let mut start = 0;
+        while start < self.entries.len() {
+            let end = chunk_end(&self.entries, start);
+            let chunk = &self.entries[start..end];
+            start = end;
+
+            {
+                let mut messages = vec![];
+                for entry in chunk {
+                    messages.push(Bind {
+                        portal: "",
+                        statement: entry.stmt.name(),
+                        formats: &[1],
+                        values: &entry.values,
+                        result_formats: &[1],
+                    });
+                    messages.push(Execute {
+                        portal: "",
+                        max_rows: 0,
+                    });
+                    if entry.sync {
+                        messages.push(Sync);
+                    }
+                }
+                // The server may otherwise hold back the results of a chunk
+                // which ends in the middle of a group.
+                if !chunk[chunk.len() - 1].sync {
+                    messages.push(Flush);
+                }
+                try!(conn.write_messages(&messages));
+            }
+
+            // After a failure the server skips messages up to the next Sync,
+            // including any Flush, so nothing is read for skipped statements.
+            for entry in chunk {
+                if failed {
+                    results.push(PipelineResult::Skipped);
+                } else {
+                    let result = try!(read_result(&mut conn, entry));
+                    if let PipelineResult::Error(_) = result {
+                        failed = true;
+                    }
+                    results.push(result);
+                }
+
+                if entry.sync {
+                    try!(conn.wait_for_ready());
+                    failed = false;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+// Returns the end of the chunk of entries starting at `start`, which always
+// contains at least one entry.
+fn chunk_end(entries: &[Entry], start: usize) -> usize {
+    let mut size = 0;
+    for (i, entry) in entries[start..].iter().enumerate() {
+        size += 32 + entry.stmt.name().len();
+        size += entry.values.iter().map(|v| 4 + v.as_ref().map_or(0, |v| v.len())).sum::<usize>();
+        if size >= CHUNK_SIZE {
+            return start + i + 1;
+        }
+    }
+    entries.len()
+}
+
+fn read_result<'a>(conn: &mut InnerConnection, entry: &Entry<'a>) -> Result<PipelineResult<'a>> {
+    match try!(conn.read_message()) {
+        BindComplete => {}
+        ErrorResponse { fields } => return db_error(conn, fields),
+        _ => bad_response!(conn),
+    }
+
+    let mut rows = vec![];
+    loop {
+        match try!(conn.read_message()) {
+            DataRow { row } => {
+                if entry.returns_rows {
+                    rows.push(row);
+                }
+            }
+            CommandComplete { tag } => {
+                if entry.returns_rows {
+                    return Ok(PipelineResult::Query(Rows::new(entry.stmt, rows)));
+                } else {
+                    return Ok(PipelineResult::Execute(util::parse_update_count(tag)));
+                }
+            }
+            EmptyQueryResponse => {
+                if entry.returns_rows {
+                    return Ok(PipelineResult::Query(Rows::new(entry.stmt, rows)));
+                } else {
+                    return Ok(PipelineResult::Execute(0));
+                }
+            }
+            ErrorResponse { fields } => return db_error(conn, fields),
+            CopyOutResponse { .. } => {
+                loop {
+                    match try!(conn.read_message()) {
+                        BCopyData { .. } => {}
+                        BCopyDone => break,
+                        ErrorResponse { fields } => return db_error(conn, fields),
+                        _ => bad_response!(conn),
+                    }
+                }
+            }
+            CopyInResponse { .. } => {
+                conn.desynchronized = true;
+                return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                                    "COPY FROM STDIN statements cannot be \
+                                                     pipelined")));
+            }
+            _ => bad_response!(conn),
+        }
+    }
+}
+
+fn db_error<'a>(conn: &mut InnerConnection,
+                fields: Vec<(u8, String)>)
+                -> Result<PipelineResult<'a>> {
+    match DbError::new_raw(fields) {
+        Ok(err) => Ok(PipelineResult::Error(Box::new(err))),
+        Err(()) => {
+            conn.desynchronized = true;
+            Err(Error::Io(bad_response()))
+        }
+    }
+}
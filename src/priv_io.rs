@@ -4,23 +4,30 @@ use std::error::Error;
 use std::io;
 use std::io::prelude::*;
 use std::fmt;
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::time::Duration;
 use bufstream::BufStream;
+#[cfg(unix)]
+use libc;
+#[cfg(feature = "unix_socket")]
+use std::path::PathBuf;
 #[cfg(feature = "unix_socket")]
 use unix_socket::UnixStream;
 #[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
 use std::os::unix::io::{AsRawFd, RawFd};
 #[cfg(windows)]
 use std::os::windows::io::{AsRawSocket, RawSocket};
 
-use {SslMode, ConnectParams, ConnectTarget};
+use {SslMode, SslNegotiation, ConnectParams, ConnectTarget};
+use conninfo;
 use error::ConnectError;
-use io::{NegotiateSsl, StreamWrapper};
+use io::{StreamWrapper, Verification};
 use message::{self, WriteMessage};
 use message::FrontendMessage::SslRequest;
 
-const DEFAULT_PORT: u16 = 5432;
+pub const DEFAULT_PORT: u16 = 5432;
 
 #[doc(hidden)]
 pub trait StreamOptions {
@@ -147,7 +154,13 @@ fn open_socket(params: &ConnectParams) -> Result<InternalStream, ConnectError> {
     let port = params.port.unwrap_or(DEFAULT_PORT);
     match params.target {
         ConnectTarget::Tcp(ref host) => {
-            Ok(try!(TcpStream::connect(&(&**host, port)).map(InternalStream::Tcp)))
+            let socket = try!(connect_tcp(host, port, params.connect_timeout));
+            // Like libpq, disable Nagle's algorithm since messages are
+            // buffered and flushed explicitly. Otherwise the tail of a large
+            // write can be held back until the server's delayed ACK.
+            try!(socket.set_nodelay(true));
+            try!(set_keepalives(&socket, params));
+            Ok(InternalStream::Tcp(socket))
         }
         #[cfg(feature = "unix_socket")]
         ConnectTarget::Unix(ref path) => {
@@ -157,17 +170,212 @@ fn open_socket(params: &ConnectParams) -> Result<InternalStream, ConnectError> {
     }
 }
 
+/// The address of the server a stream is connected to.
+#[derive(Debug, Clone)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    #[cfg(feature = "unix_socket")]
+    Unix(PathBuf),
+}
+
+/// Returns the address of the server `stream` is connected to.
+pub fn peer_addr(stream: &Stream) -> io::Result<PeerAddr> {
+    match stream.0 {
+        InternalStream::Tcp(ref s) => s.peer_addr().map(PeerAddr::Tcp),
+        #[cfg(feature = "unix_socket")]
+        InternalStream::Unix(ref s) => {
+            let addr = try!(s.peer_addr());
+            match addr.as_pathname() {
+                Some(path) => Ok(PeerAddr::Unix(path.to_owned())),
+                None => Err(io::Error::new(io::ErrorKind::Other, "the Unix socket has no path")),
+            }
+        }
+    }
+}
+
+fn open_peer(addr: &PeerAddr, timeout: Option<Duration>) -> io::Result<InternalStream> {
+    match *addr {
+        PeerAddr::Tcp(ref addr) => {
+            let socket = match timeout {
+                Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            socket.map(InternalStream::Tcp)
+        }
+        #[cfg(feature = "unix_socket")]
+        PeerAddr::Unix(ref path) => UnixStream::connect(path).map(InternalStream::Unix),
+    }
+}
+
+fn connect_tcp(host: &str, port: u16, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return TcpStream::connect((host, port)),
+    };
+
+    let mut last_err = None;
+    for addr in try!((host, port).to_socket_addrs()) {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(socket) => return Ok(socket),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput,
+                       "could not resolve to any addresses")
+    }))
+}
+
+fn set_keepalives(socket: &TcpStream, params: &ConnectParams) -> io::Result<()> {
+    if !params.keepalives {
+        return Ok(());
+    }
+
+    // The socket options take whole seconds, so round up rather than
+    // truncating a sub-second time to zero, which is invalid.
+    match params.keepalives_idle {
+        Some(idle) => {
+            try!(socket.set_keepalive(Some(Duration::from_secs(conninfo::round_up(idle, 1000)))))
+        }
+        None => try!(enable_keepalive(socket)),
+    }
+
+    set_linux_socket_options(socket, params)
+}
+
+#[cfg(unix)]
+fn enable_keepalive(socket: &TcpStream) -> io::Result<()> {
+    setsockopt(socket, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)
+}
+
+#[cfg(windows)]
+fn enable_keepalive(socket: &TcpStream) -> io::Result<()> {
+    // Windows can't enable keepalives without setting the idle time, so use
+    // its default of two hours.
+    socket.set_keepalive(Some(Duration::from_secs(2 * 60 * 60)))
+}
+
+#[cfg(target_os = "linux")]
+fn set_linux_socket_options(socket: &TcpStream, params: &ConnectParams) -> io::Result<()> {
+    if let Some(interval) = params.keepalives_interval {
+        let secs = try!(to_c_int("keepalives_interval", conninfo::round_up(interval, 1000)));
+        try!(setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs));
+    }
+    if let Some(count) = params.keepalives_count {
+        let count = try!(to_c_int("keepalives_count", count as u64));
+        try!(setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, count));
+    }
+    if let Some(timeout) = params.tcp_user_timeout {
+        let millis = timeout.as_secs()
+                            .checked_mul(1000)
+                            .and_then(|m| m.checked_add(timeout.subsec_nanos() as u64 / 1000000));
+        let millis = try!(to_c_int("tcp_user_timeout", millis.unwrap_or(u64::max_value())));
+        try!(setsockopt(socket, libc::IPPROTO_TCP, libc::TCP_USER_TIMEOUT, millis));
+    }
+    Ok(())
+}
+
+// Values are rejected rather than truncated if the socket option can't
+// represent them.
+#[cfg(target_os = "linux")]
+fn to_c_int(key: &str, value: u64) -> io::Result<libc::c_int> {
+    if value > libc::c_int::max_value() as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  format!("{} value {} is out of range", key, value)));
+    }
+    Ok(value as libc::c_int)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_linux_socket_options(_: &TcpStream, _: &ConnectParams) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn setsockopt(socket: &TcpStream,
+              level: libc::c_int,
+              name: libc::c_int,
+              value: libc::c_int)
+              -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(socket.as_raw_fd(),
+                         level,
+                         name,
+                         &value as *const _ as *const libc::c_void,
+                         mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
 pub fn initialize_stream(params: &ConnectParams,
                          ssl: SslMode)
                          -> Result<Box<StreamWrapper>, ConnectError> {
-    let mut socket = Stream(try!(open_socket(params)));
+    let host = match params.target {
+        ConnectTarget::Tcp(ref host) => Some(&**host),
+        #[cfg(feature = "unix_socket")]
+        ConnectTarget::Unix(_) => None,
+    };
+    negotiate(|| open_socket(params), host, params.ssl_negotiation, ssl)
+}
+
+/// Like `initialize_stream`, but connects to the address of an existing
+/// connection rather than resolving the connection parameters again.
+pub fn initialize_peer_stream(addr: &PeerAddr,
+                              host: Option<&str>,
+                              negotiation: SslNegotiation,
+                              timeout: Option<Duration>,
+                              ssl: SslMode)
+                              -> Result<Box<StreamWrapper>, ConnectError> {
+    negotiate(|| open_peer(addr, timeout).map_err(ConnectError::Io), host, negotiation, ssl)
+}
+
+// `host` is `None` for Unix sockets.
+fn negotiate<F>(open: F,
+                host: Option<&str>,
+                negotiation: SslNegotiation,
+                ssl: SslMode)
+                -> Result<Box<StreamWrapper>, ConnectError>
+    where F: FnOnce() -> Result<InternalStream, ConnectError>
+{
+    let direct = negotiation == SslNegotiation::Direct;
 
-    let (ssl_required, negotiator) = match ssl {
-        SslMode::None => return Ok(Box::new(socket)),
-        SslMode::Prefer(negotiator) => (false, negotiator),
-        SslMode::Require(negotiator) => (true, negotiator),
+    // allow only uses SSL when retrying, which is handled by the caller
+    let (ssl_required, verification, negotiator) = match ssl {
+        SslMode::None | SslMode::Allow(_) | SslMode::Prefer(_) if direct => {
+            let err: Box<Error + Sync + Send> = "direct SSL negotiation requires an SSL mode of \
+                                                 require or stronger"
+                                                    .into();
+            return Err(ConnectError::ConnectParams(err));
+        }
+        SslMode::None | SslMode::Allow(_) => return Ok(Box::new(Stream(try!(open())))),
+        SslMode::Prefer(negotiator) => (false, Verification::Default, negotiator),
+        SslMode::Require(negotiator) => (true, Verification::Default, negotiator),
+        SslMode::VerifyCa(negotiator) => (true, Verification::Ca, negotiator),
+        SslMode::VerifyFull(negotiator) => (true, Verification::Full, negotiator),
     };
 
+    if direct {
+        let host = match host {
+            Some(host) => host,
+            None => {
+                let err: Box<Error + Sync + Send> = "Postgres does not support SSL over Unix \
+                                                     sockets"
+                                                        .into();
+                return Err(ConnectError::Ssl(err));
+            }
+        };
+        let socket = Stream(try!(open()));
+        return negotiator.negotiate_ssl_direct(host, socket, verification)
+                         .map_err(ConnectError::Ssl);
+    }
+
+    let mut socket = Stream(try!(open()));
+
     try!(socket.write_message(&SslRequest { code: message::SSL_CODE }));
     try!(socket.flush());
 
@@ -181,11 +389,10 @@ pub fn initialize_stream(params: &ConnectParams,
     }
 
     // Postgres doesn't support SSL over unix sockets
-    let host = match params.target {
-        ConnectTarget::Tcp(ref host) => host,
-        #[cfg(feature = "unix_socket")]
-        ConnectTarget::Unix(_) => return Err(ConnectError::Io(::bad_response())),
+    let host = match host {
+        Some(host) => host,
+        None => return Err(ConnectError::Io(::bad_response())),
     };
 
-    negotiator.negotiate_ssl(host, socket).map_err(ConnectError::Ssl)
+    negotiator.negotiate_ssl_verified(host, socket, verification).map_err(ConnectError::Ssl)
 }
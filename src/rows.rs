@@ -1,17 +1,21 @@
 //! Query result rows.
 
-use std::ascii::AsciiExt;
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::VecDeque;
 use std::fmt;
+use std::io;
 use std::ops::Deref;
 use std::slice;
+use std::sync::Arc;
 
-use {Result, Transaction, read_rows, DbErrorNew, SessionInfoNew, RowsNew, LazyRowsNew,
-     StatementInternals, WrongTypeNew};
+use {Result, Connection, StatementInfo, Transaction, read_rows, bad_response, DbErrorNew,
+     SessionInfoNew, RowsNew, LazyRowsNew, RowStreamNew, StatementInternals, WrongTypeNew};
 use types::{FromSql, SessionInfo, WrongType};
 use stmt::{Statement, Column};
-use error::Error;
+use error::{Error, DbError};
+use util;
+use message::BackendMessage::*;
 use message::FrontendMessage::*;
 
 enum StatementContainer<'a> {
@@ -30,6 +34,40 @@ impl<'a> Deref for StatementContainer<'a> {
     }
 }
 
+// The statement of a `Row`. The rows of a `RowStream` which owns its
+// statement can't borrow it, so they share its information instead.
+enum StatementRef<'a> {
+    Borrowed(&'a Statement<'a>),
+    Shared(&'a Connection, Arc<StatementInfo>),
+}
+
+impl<'a> StatementRef<'a> {
+    fn conn(&self) -> &'a Connection {
+        match *self {
+            StatementRef::Borrowed(stmt) => stmt.conn(),
+            StatementRef::Shared(conn, _) => conn,
+        }
+    }
+
+    fn columns(&self) -> &[Column] {
+        match *self {
+            StatementRef::Borrowed(stmt) => stmt.columns(),
+            StatementRef::Shared(_, ref info) => &info.columns,
+        }
+    }
+
+    fn with<F, T>(&self, f: F) -> T
+        where F: FnOnce(&Statement) -> T
+    {
+        match *self {
+            StatementRef::Borrowed(stmt) => f(stmt),
+            StatementRef::Shared(conn, ref info) => {
+                f(&Statement::new(conn, info.clone(), Cell::new(0), true))
+            }
+        }
+    }
+}
+
 /// The resulting rows of a query.
 pub struct Rows<'stmt> {
     stmt: StatementContainer<'stmt>,
@@ -79,7 +117,7 @@ impl<'stmt> Rows<'stmt> {
     /// Panics if `idx` is out of bounds.
     pub fn get<'a>(&'a self, idx: usize) -> Row<'a> {
         Row {
-            stmt: &*self.stmt,
+            stmt: StatementRef::Borrowed(&*self.stmt),
             data: Cow::Borrowed(&self.data[idx]),
         }
     }
@@ -114,7 +152,7 @@ impl<'a> Iterator for Iter<'a> {
     fn next(&mut self) -> Option<Row<'a>> {
         self.iter.next().map(|row| {
             Row {
-                stmt: &*self.stmt,
+                stmt: StatementRef::Borrowed(self.stmt),
                 data: Cow::Borrowed(row),
             }
         })
@@ -129,7 +167,7 @@ impl<'a> DoubleEndedIterator for Iter<'a> {
     fn next_back(&mut self) -> Option<Row<'a>> {
         self.iter.next_back().map(|row| {
             Row {
-                stmt: &*self.stmt,
+                stmt: StatementRef::Borrowed(self.stmt),
                 data: Cow::Borrowed(row),
             }
         })
@@ -140,15 +178,17 @@ impl<'a> ExactSizeIterator for Iter<'a> {}
 
 /// A single result row of a query.
 pub struct Row<'a> {
-    stmt: &'a Statement<'a>,
+    stmt: StatementRef<'a>,
     data: Cow<'a, [Option<Vec<u8>>]>,
 }
 
 impl<'a> fmt::Debug for Row<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("Row")
-           .field("statement", self.stmt)
-           .finish()
+        self.stmt.with(|stmt| {
+            fmt.debug_struct("Row")
+               .field("statement", stmt)
+               .finish()
+        })
     }
 }
 
@@ -215,7 +255,7 @@ impl<'a> Row<'a> {
         where I: RowIndex,
               T: FromSql
     {
-        let idx = match idx.idx(self.stmt) {
+        let idx = match self.stmt.with(|stmt| idx.idx(stmt)) {
             Some(idx) => idx,
             None => return None,
         };
@@ -225,9 +265,10 @@ impl<'a> Row<'a> {
             return Some(Err(Error::Conversion(Box::new(WrongType::new(ty.clone())))));
         }
         let conn = self.stmt.conn().conn.borrow();
+        let ctx = SessionInfo::new(&conn.parameters);
         let value = match self.data[idx] {
-            Some(ref data) => FromSql::from_sql(ty, &mut &**data, &SessionInfo::new(&*conn)),
-            None => FromSql::from_sql_null(ty, &SessionInfo::new(&*conn)),
+            Some(ref data) => FromSql::from_sql(ty, &mut &**data, &ctx),
+            None => FromSql::from_sql_null(ty, &ctx),
         };
         Some(value)
     }
@@ -240,7 +281,7 @@ impl<'a> Row<'a> {
     pub fn get_bytes<I>(&self, idx: I) -> Option<&[u8]>
         where I: RowIndex + fmt::Debug
     {
-        match idx.idx(self.stmt) {
+        match self.stmt.with(|stmt| idx.idx(stmt)) {
             Some(idx) => self.data[idx].as_ref().map(|e| &**e),
             None => panic!("invalid index {:?}", idx),
         }
@@ -268,14 +309,7 @@ impl RowIndex for usize {
 impl<'a> RowIndex for &'a str {
     #[inline]
     fn idx(&self, stmt: &Statement) -> Option<usize> {
-        if let Some(idx) = stmt.columns().iter().position(|d| d.name() == *self) {
-            return Some(idx);
-        };
-
-        // FIXME ASCII-only case insensitivity isn't really the right thing to
-        // do. Postgres itself uses a dubious wrapper around tolower and JDBC
-        // uses the US locale.
-        stmt.columns().iter().position(|d| d.name().eq_ignore_ascii_case(*self))
+        util::column_index(stmt.columns(), self)
     }
 }
 
@@ -374,7 +408,7 @@ impl<'trans, 'stmt> Iterator for LazyRows<'trans, 'stmt> {
 
         self.data.pop_front().map(|r| {
             Ok(Row {
-                stmt: self.stmt,
+                stmt: StatementRef::Borrowed(self.stmt),
                 data: Cow::Owned(r),
             })
         })
@@ -390,3 +424,169 @@ impl<'trans, 'stmt> Iterator for LazyRows<'trans, 'stmt> {
         (lower, upper)
     }
 }
+
+/// An iterator over the resulting rows of a query which reads each row from
+/// the connection as it is requested.
+///
+/// Unlike `LazyRows`, a `RowStream` does not require a transaction, since
+/// the statement is executed to completion in a single pass rather than in
+/// batches from a portal. Only one row is held in memory at a time, so
+/// arbitrarily large results can be processed.
+///
+/// The connection cannot be used for anything else until the stream has
+/// been consumed or dropped; other operations will return an error. If the
+/// stream is dropped before it has been consumed, the remaining rows are read
+/// and discarded.
+pub struct RowStream<'stmt> {
+    stmt: StatementContainer<'stmt>,
+    finished: bool,
+}
+
+impl<'stmt> RowStreamNew<'stmt> for RowStream<'stmt> {
+    fn new(stmt: &'stmt Statement<'stmt>) -> RowStream<'stmt> {
+        RowStream {
+            stmt: StatementContainer::Borrowed(stmt),
+            finished: false,
+        }
+    }
+
+    fn new_owned(stmt: Statement<'stmt>) -> RowStream<'stmt> {
+        RowStream {
+            stmt: StatementContainer::Owned(stmt),
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Drop for RowStream<'a> {
+    fn drop(&mut self) {
+        let _ = self.finish_inner();
+    }
+}
+
+impl<'a> fmt::Debug for RowStream<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RowStream")
+           .field("columns", &self.columns())
+           .field("finished", &self.finished)
+           .finish()
+    }
+}
+
+impl<'stmt> RowStream<'stmt> {
+    fn read_row(&mut self) -> Result<Option<Vec<Option<Vec<u8>>>>> {
+        let mut conn = self.stmt.conn().conn.borrow_mut();
+        loop {
+            let message = match conn.read_message() {
+                Ok(message) => message,
+                Err(err) => {
+                    conn.streaming = false;
+                    return Err(Error::Io(err));
+                }
+            };
+
+            match message {
+                DataRow { row } => return Ok(Some(row)),
+                EmptyQueryResponse | CommandComplete { .. } => {
+                    conn.streaming = false;
+                    try!(conn.wait_for_ready());
+                    return Ok(None);
+                }
+                ErrorResponse { fields } => {
+                    conn.streaming = false;
+                    try!(conn.wait_for_ready());
+                    return DbError::new(fields);
+                }
+                CopyInResponse { .. } => {
+                    conn.streaming = false;
+                    try!(conn.write_messages(&[CopyFail {
+                                                   message: "COPY queries cannot be directly \
+                                                             executed",
+                                               },
+                                               Sync]));
+                    conn.streaming = true;
+                }
+                CopyOutResponse { .. } => {
+                    conn.streaming = false;
+                    loop {
+                        match try!(conn.read_message()) {
+                            ReadyForQuery { .. } => break,
+                            _ => {}
+                        }
+                    }
+                    return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                                        "COPY queries cannot be directly \
+                                                         executed")));
+                }
+                _ => {
+                    conn.streaming = false;
+                    conn.desynchronized = true;
+                    return Err(Error::Io(bad_response()));
+                }
+            }
+        }
+    }
+
+    fn finish_inner(&mut self) -> Result<()> {
+        while !self.finished {
+            if let Err(err) = self.next_row() {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn next_row(&mut self) -> Result<Option<Row<'stmt>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        match self.read_row() {
+            Ok(Some(row)) => {
+                let stmt = match self.stmt {
+                    StatementContainer::Borrowed(stmt) => StatementRef::Borrowed(stmt),
+                    StatementContainer::Owned(ref stmt) => {
+                        StatementRef::Shared(stmt.conn(), stmt.info().clone())
+                    }
+                };
+                Ok(Some(Row {
+                    stmt: stmt,
+                    data: Cow::Owned(row),
+                }))
+            }
+            Ok(None) => {
+                self.finished = true;
+                Ok(None)
+            }
+            Err(err) => {
+                self.finished = true;
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns a slice describing the columns of the `RowStream`.
+    pub fn columns(&self) -> &[Column] {
+        self.stmt.columns()
+    }
+
+    /// Consumes the `RowStream`, discarding any remaining rows.
+    ///
+    /// Functionally identical to the `Drop` implementation on `RowStream`
+    /// except that it returns any error to the caller.
+    pub fn finish(mut self) -> Result<()> {
+        self.finish_inner()
+    }
+}
+
+impl<'stmt> Iterator for RowStream<'stmt> {
+    type Item = Result<Row<'stmt>>;
+
+    fn next(&mut self) -> Option<Result<Row<'stmt>>> {
+        match self.next_row() {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
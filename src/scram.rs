@@ -0,0 +1,429 @@
+//! SCRAM-SHA-256 authentication, as described in RFC 5802 and RFC 7677.
+use rand::{self, Rng};
+use std::io;
+use std::iter;
+use std::str;
+
+use sha256::{self, Sha256};
+use util::{base64_decode, base64_encode};
+
+/// The name of the SCRAM-SHA-256 SASL mechanism.
+pub const SCRAM_SHA_256: &'static str = "SCRAM-SHA-256";
+/// The name of the SCRAM-SHA-256-PLUS SASL mechanism.
+pub const SCRAM_SHA_256_PLUS: &'static str = "SCRAM-SHA-256-PLUS";
+
+/// The channel binding configuration for a SCRAM authentication exchange.
+pub enum ChannelBinding {
+    /// The client does not support channel binding.
+    Unsupported,
+    /// The client supports channel binding, but believes the server does not.
+    Unrequested,
+    /// The client requires `tls-server-end-point` channel binding with the
+    /// provided certificate hash.
+    TlsServerEndPoint(Vec<u8>),
+}
+
+impl ChannelBinding {
+    fn gs2_header(&self) -> &'static str {
+        match *self {
+            ChannelBinding::Unsupported => "n,,",
+            ChannelBinding::Unrequested => "y,,",
+            ChannelBinding::TlsServerEndPoint(_) => "p=tls-server-end-point,,",
+        }
+    }
+
+    fn cbind_data(&self) -> &[u8] {
+        match *self {
+            ChannelBinding::Unsupported | ChannelBinding::Unrequested => &[],
+            ChannelBinding::TlsServerEndPoint(ref buf) => buf,
+        }
+    }
+}
+
+const NONCE_LENGTH: usize = 24;
+
+// Each iteration costs two HMAC computations, so a malicious server could
+// otherwise make the client hang by requesting billions of them. Postgres
+// uses 4096 by default.
+const MAX_ITERATION_COUNT: u32 = 1000000;
+
+enum State {
+    Update {
+        nonce: String,
+        password: Vec<u8>,
+        channel_binding: ChannelBinding,
+    },
+    Finish {
+        salted_password: [u8; sha256::OUTPUT_LEN],
+        auth_message: String,
+    },
+    Done,
+}
+
+/// The client side of a SCRAM-SHA-256 exchange.
+///
+/// The `message` method returns the message to send to the server at each
+/// step. `update` should be called with the contents of the server's
+/// `AuthenticationSASLContinue` message and `finish` with the contents of its
+/// `AuthenticationSASLFinal` message.
+///
+/// The password is used as-is; SASLprep normalization is not applied, which
+/// matches libpq's behavior for passwords that are not valid UTF-8 or that
+/// SASLprep would reject.
+pub struct ScramSha256 {
+    message: String,
+    state: State,
+}
+
+impl ScramSha256 {
+    /// Constructs a new instance which will use the provided password and
+    /// channel binding configuration.
+    pub fn new(password: &[u8], channel_binding: ChannelBinding) -> ScramSha256 {
+        let mut rng = rand::thread_rng();
+        let nonce = (0..NONCE_LENGTH)
+                        .map(|_| {
+                            // printable ASCII, excluding ','
+                            let mut v = rng.gen_range(0x21u8, 0x7e);
+                            if v == b',' {
+                                v = 0x7e;
+                            }
+                            v as char
+                        })
+                        .collect::<String>();
+
+        // the username is taken from the startup message, so it's left empty
+        let message = format!("{}n=,r={}", channel_binding.gs2_header(), nonce);
+
+        ScramSha256 {
+            message: message,
+            state: State::Update {
+                nonce: nonce,
+                password: password.to_vec(),
+                channel_binding: channel_binding,
+            },
+        }
+    }
+
+    /// Returns the message which should be sent to the backend next.
+    pub fn message(&self) -> &[u8] {
+        if let State::Done = self.state {
+            panic!("invalid SCRAM state");
+        }
+        self.message.as_bytes()
+    }
+
+    /// Updates the state machine with the response from the backend.
+    ///
+    /// This should be called when an `AuthenticationSASLContinue` message is
+    /// received.
+    pub fn update(&mut self, message: &[u8]) -> io::Result<()> {
+        let (client_nonce, password, channel_binding) =
+            match ::std::mem::replace(&mut self.state, State::Done) {
+                State::Update { nonce, password, channel_binding } => {
+                    (nonce, password, channel_binding)
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::Other, "invalid SCRAM state")),
+            };
+
+        let message = try!(str::from_utf8(message)
+                               .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+        let parsed = try!(Parser::new(message).server_first_message());
+
+        // The server's nonce must extend ours with a part of its own.
+        if !parsed.nonce.starts_with(&client_nonce) || parsed.nonce.len() == client_nonce.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid nonce"));
+        }
+        if parsed.iteration_count == 0 || parsed.iteration_count > MAX_ITERATION_COUNT {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("invalid iteration count {}",
+                                              parsed.iteration_count)));
+        }
+
+        let salt = try!(base64_decode(parsed.salt));
+        let salted_password = hi(&password, &salt, parsed.iteration_count);
+
+        let client_key = sha256::hmac(&salted_password, b"Client Key");
+        let mut hash = Sha256::new();
+        hash.input(&client_key);
+        let stored_key = hash.result();
+
+        let gs2_header = channel_binding.gs2_header();
+        let client_first_message_bare = &self.message[gs2_header.len()..];
+        let mut cbind_input = gs2_header.as_bytes().to_vec();
+        cbind_input.extend_from_slice(channel_binding.cbind_data());
+        let without_proof = format!("c={},r={}", base64_encode(&cbind_input), parsed.nonce);
+        let auth_message = format!("{},{},{}",
+                                   client_first_message_bare,
+                                   message,
+                                   without_proof);
+
+        let client_signature = sha256::hmac(&stored_key, auth_message.as_bytes());
+        let mut client_proof = client_key;
+        for (proof, signature) in client_proof.iter_mut().zip(client_signature.iter()) {
+            *proof ^= *signature;
+        }
+
+        self.message = format!("{},p={}", without_proof, base64_encode(&client_proof));
+        self.state = State::Finish {
+            salted_password: salted_password,
+            auth_message: auth_message,
+        };
+        Ok(())
+    }
+
+    /// Finalizes the authentication process.
+    ///
+    /// This should be called when the backend sends an
+    /// `AuthenticationSASLFinal` message. An error is returned if the server
+    /// could not prove that it knows the password.
+    pub fn finish(&mut self, message: &[u8]) -> io::Result<()> {
+        let (salted_password, auth_message) = match ::std::mem::replace(&mut self.state,
+                                                                        State::Done) {
+            State::Finish { salted_password, auth_message } => (salted_password, auth_message),
+            _ => return Err(io::Error::new(io::ErrorKind::Other, "invalid SCRAM state")),
+        };
+
+        let message = try!(str::from_utf8(message)
+                               .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+        let verifier = match try!(Parser::new(message).server_final_message()) {
+            ServerFinalMessage::Error(e) => {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          format!("SCRAM error: {}", e)));
+            }
+            ServerFinalMessage::Verifier(verifier) => verifier,
+        };
+
+        let verifier = try!(base64_decode(verifier));
+
+        let server_key = sha256::hmac(&salted_password, b"Server Key");
+        let server_signature = sha256::hmac(&server_key, auth_message.as_bytes());
+
+        // compare without short circuiting
+        let mut diff = (verifier.len() ^ server_signature.len()) as u8;
+        for (a, b) in verifier.iter().zip(server_signature.iter()) {
+            diff |= *a ^ *b;
+        }
+        if diff != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "SCRAM server signature mismatch"));
+        }
+
+        Ok(())
+    }
+}
+
+// Hi(str, salt, i) from RFC 5802, which is PBKDF2 with HMAC-SHA-256 and a
+// single output block.
+fn hi(password: &[u8], salt: &[u8], iterations: u32) -> [u8; sha256::OUTPUT_LEN] {
+    let mut message = salt.to_vec();
+    message.extend_from_slice(&[0, 0, 0, 1]);
+    let mut prev = sha256::hmac(password, &message);
+    let mut hi = prev;
+
+    for _ in 1..iterations {
+        prev = sha256::hmac(password, &prev);
+        for (hi, prev) in hi.iter_mut().zip(prev.iter()) {
+            *hi ^= *prev;
+        }
+    }
+
+    hi
+}
+
+struct ServerFirstMessage<'a> {
+    nonce: &'a str,
+    salt: &'a str,
+    iteration_count: u32,
+}
+
+enum ServerFinalMessage<'a> {
+    Error(&'a str),
+    Verifier(&'a str),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    it: iter::Peekable<str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Parser<'a> {
+        Parser {
+            s: s,
+            it: s.char_indices().peekable(),
+        }
+    }
+
+    fn eat(&mut self, target: char) -> io::Result<()> {
+        match self.it.next() {
+            Some((_, c)) if c == target => Ok(()),
+            Some((i, c)) => {
+                let m = format!("unexpected character at byte {}: expected `{}` but got `{}`",
+                                i,
+                                target,
+                                c);
+                Err(io::Error::new(io::ErrorKind::InvalidInput, m))
+            }
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected EOF")),
+        }
+    }
+
+    fn take_while<F>(&mut self, f: F) -> io::Result<&'a str>
+        where F: Fn(char) -> bool
+    {
+        let start = match self.it.peek() {
+            Some(&(i, _)) => i,
+            None => return Ok(""),
+        };
+
+        loop {
+            match self.it.peek() {
+                Some(&(_, c)) if f(c) => {
+                    self.it.next();
+                }
+                Some(&(i, _)) => return Ok(&self.s[start..i]),
+                None => return Ok(&self.s[start..]),
+            }
+        }
+    }
+
+    fn printable(&mut self) -> io::Result<&'a str> {
+        self.take_while(|c| match c {
+            '\x21'...'\x2b' | '\x2d'...'\x7e' => true,
+            _ => false,
+        })
+    }
+
+    fn nonce(&mut self) -> io::Result<&'a str> {
+        try!(self.eat('r'));
+        try!(self.eat('='));
+        self.printable()
+    }
+
+    fn base64(&mut self) -> io::Result<&'a str> {
+        self.take_while(|c| match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | '/' | '+' | '=' => true,
+            _ => false,
+        })
+    }
+
+    fn salt(&mut self) -> io::Result<&'a str> {
+        try!(self.eat('s'));
+        try!(self.eat('='));
+        self.base64()
+    }
+
+    fn posit_number(&mut self) -> io::Result<u32> {
+        let n = try!(self.take_while(|c| match c {
+            '0'...'9' => true,
+            _ => false,
+        }));
+        n.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn iteration_count(&mut self) -> io::Result<u32> {
+        try!(self.eat('i'));
+        try!(self.eat('='));
+        self.posit_number()
+    }
+
+    fn eof(&mut self) -> io::Result<()> {
+        match self.it.peek() {
+            Some(&(i, _)) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                   format!("unexpected trailing data at byte {}", i)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn server_first_message(&mut self) -> io::Result<ServerFirstMessage<'a>> {
+        let nonce = try!(self.nonce());
+        try!(self.eat(','));
+        let salt = try!(self.salt());
+        try!(self.eat(','));
+        let iteration_count = try!(self.iteration_count());
+        try!(self.eof());
+
+        Ok(ServerFirstMessage {
+            nonce: nonce,
+            salt: salt,
+            iteration_count: iteration_count,
+        })
+    }
+
+    fn value(&mut self) -> io::Result<&'a str> {
+        self.take_while(|c| match c {
+            '\0' | '=' | ',' => false,
+            _ => true,
+        })
+    }
+
+    fn server_error(&mut self) -> io::Result<Option<&'a str>> {
+        match self.it.peek() {
+            Some(&(_, 'e')) => {}
+            _ => return Ok(None),
+        }
+
+        try!(self.eat('e'));
+        try!(self.eat('='));
+        self.value().map(Some)
+    }
+
+    fn verifier(&mut self) -> io::Result<&'a str> {
+        try!(self.eat('v'));
+        try!(self.eat('='));
+        self.base64()
+    }
+
+    fn server_final_message(&mut self) -> io::Result<ServerFinalMessage<'a>> {
+        let message = match try!(self.server_error()) {
+            Some(error) => ServerFinalMessage::Error(error),
+            None => ServerFinalMessage::Verifier(try!(self.verifier())),
+        };
+        try!(self.eof());
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scram::{ChannelBinding, ScramSha256, State};
+
+    fn client_nonce(scram: &ScramSha256) -> String {
+        match scram.state {
+            State::Update { ref nonce, .. } => nonce.clone(),
+            _ => panic!("unexpected state"),
+        }
+    }
+
+    fn update(server_nonce: &str, iteration_count: u32) -> bool {
+        let mut scram = ScramSha256::new(b"password", ChannelBinding::Unsupported);
+        let nonce = client_nonce(&scram) + server_nonce;
+        let message = format!("r={},s=c2FsdA==,i={}", nonce, iteration_count);
+        scram.update(message.as_bytes()).is_ok()
+    }
+
+    #[test]
+    fn server_first_message() {
+        assert!(update("server", 4096));
+    }
+
+    #[test]
+    fn nonce_without_server_part() {
+        assert!(!update("", 4096));
+    }
+
+    #[test]
+    fn foreign_nonce() {
+        let mut scram = ScramSha256::new(b"password", ChannelBinding::Unsupported);
+        assert!(scram.update(b"r=abcdefghijklmnopqrstuvwxyz,s=c2FsdA==,i=4096").is_err());
+    }
+
+    #[test]
+    fn iteration_count_limit() {
+        assert!(!update("server", 0));
+        assert!(!update("server", 1000001));
+        assert!(!update("server", 2147483647));
+    }
+}
@@ -0,0 +1,115 @@
+//! Connection service file support.
+//!
+//! See the [Postgres documentation](http://www.postgresql.org/docs/current/static/libpq-pgservice.html)
+//! for details on the file format.
+use std::env;
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Looks up the keyword/value pairs of the service `name`.
+///
+/// The user's service file, specified by the `PGSERVICEFILE` environment
+/// variable or `~/.pg_service.conf` by default, is searched first, followed
+/// by `pg_service.conf` in the directory specified by the `PGSYSCONFDIR`
+/// environment variable, or `/etc/postgresql-common` by default.
+pub fn find(name: &str) -> Result<Vec<(String, String)>, Box<StdError + Sync + Send>> {
+    for path in user_file().into_iter().chain(system_file()) {
+        if let Some(pairs) = try!(search(&path, name)) {
+            return Ok(pairs);
+        }
+    }
+
+    Err(format!("definition of service `{}` not found", name).into())
+}
+
+fn user_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PGSERVICEFILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    default_user_file()
+}
+
+#[cfg(unix)]
+#[allow(deprecated)]
+fn default_user_file() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".pg_service.conf"))
+}
+
+#[cfg(windows)]
+fn default_user_file() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|appdata| {
+        PathBuf::from(appdata).join("postgresql").join(".pg_service.conf")
+    })
+}
+
+fn system_file() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("PGSYSCONFDIR") {
+        return Some(PathBuf::from(dir).join("pg_service.conf"));
+    }
+
+    default_system_file()
+}
+
+#[cfg(unix)]
+fn default_system_file() -> Option<PathBuf> {
+    Some(PathBuf::from("/etc/postgresql-common/pg_service.conf"))
+}
+
+#[cfg(windows)]
+fn default_system_file() -> Option<PathBuf> {
+    None
+}
+
+fn search(path: &Path,
+          name: &str)
+          -> Result<Option<Vec<(String, String)>>, Box<StdError + Sync + Send>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(format!("unable to read service file {}: {}", path.display(), e).into())
+        }
+    };
+
+    let mut pairs = None;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = try!(line);
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(syntax_error(path, i));
+            }
+            if pairs.is_some() {
+                break;
+            }
+            if &line[1..line.len() - 1] == name {
+                pairs = Some(vec![]);
+            }
+            continue;
+        }
+
+        if let Some(ref mut pairs) = pairs {
+            let mut it = line.splitn(2, '=');
+            match (it.next(), it.next()) {
+                (Some(key), Some(value)) => {
+                    pairs.push((key.trim().to_owned(), value.trim().to_owned()))
+                }
+                _ => return Err(syntax_error(path, i)),
+            }
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn syntax_error(path: &Path, line: usize) -> Box<StdError + Sync + Send> {
+    format!("syntax error in service file {}, line {}", path.display(), line + 1).into()
+}
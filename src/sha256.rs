@@ -0,0 +1,249 @@
+use std::cmp;
+
+const BLOCK_LEN: usize = 64;
+
+/// The length in bytes of a SHA-256 digest.
+pub const OUTPUT_LEN: usize = 32;
+
+const K: [u32; 64] = [0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+                      0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+                      0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+                      0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+                      0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+                      0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+                      0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+                      0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+                      0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+                      0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+                      0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2];
+
+const H0: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+                      0x1f83d9ab, 0x5be0cd19];
+
+/// An incremental SHA-256 hasher.
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; BLOCK_LEN],
+    buffer_idx: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    /// Construct a new instance.
+    pub fn new() -> Sha256 {
+        Sha256 {
+            state: H0,
+            buffer: [0; BLOCK_LEN],
+            buffer_idx: 0,
+            length: 0,
+        }
+    }
+
+    /// Feeds data into the hasher.
+    pub fn input(&mut self, mut input: &[u8]) {
+        self.length += input.len() as u64;
+
+        if self.buffer_idx != 0 {
+            let len = cmp::min(BLOCK_LEN - self.buffer_idx, input.len());
+            self.buffer[self.buffer_idx..self.buffer_idx + len].copy_from_slice(&input[..len]);
+            self.buffer_idx += len;
+            input = &input[len..];
+
+            if self.buffer_idx < BLOCK_LEN {
+                return;
+            }
+            let block = self.buffer;
+            self.process_block(&block);
+            self.buffer_idx = 0;
+        }
+
+        while input.len() >= BLOCK_LEN {
+            self.process_block(&input[..BLOCK_LEN]);
+            input = &input[BLOCK_LEN..];
+        }
+
+        self.buffer[..input.len()].copy_from_slice(input);
+        self.buffer_idx = input.len();
+    }
+
+    /// Returns the digest of the data fed into the hasher.
+    pub fn result(&mut self) -> [u8; OUTPUT_LEN] {
+        let bit_len = self.length.wrapping_mul(8);
+
+        let mut padding = [0; BLOCK_LEN + 8];
+        padding[0] = 0x80;
+        let pad_len = if self.buffer_idx < BLOCK_LEN - 8 {
+            BLOCK_LEN - 8 - self.buffer_idx
+        } else {
+            2 * BLOCK_LEN - 8 - self.buffer_idx
+        };
+        for (i, b) in padding[pad_len..pad_len + 8].iter_mut().enumerate() {
+            *b = (bit_len >> (56 - 8 * i)) as u8;
+        }
+
+        let length = self.length;
+        self.input(&padding[..pad_len + 8]);
+        self.length = length;
+        debug_assert!(self.buffer_idx == 0);
+
+        let mut out = [0; OUTPUT_LEN];
+        for (chunk, &word) in out.chunks_mut(4).zip(self.state.iter()) {
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = (word >> (24 - 8 * i)) as u8;
+            }
+        }
+        out
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in block.chunks(4).enumerate() {
+            w[i] = chunk.iter().fold(0, |word, &b| word << 8 | b as u32);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut a = self.state[0];
+        let mut b = self.state[1];
+        let mut c = self.state[2];
+        let mut d = self.state[3];
+        let mut e = self.state[4];
+        let mut f = self.state[5];
+        let mut g = self.state[6];
+        let mut h = self.state[7];
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+        self.state[4] = self.state[4].wrapping_add(e);
+        self.state[5] = self.state[5].wrapping_add(f);
+        self.state[6] = self.state[6].wrapping_add(g);
+        self.state[7] = self.state[7].wrapping_add(h);
+    }
+}
+
+/// Computes the HMAC-SHA-256 of `message` keyed with `key`.
+pub fn hmac(key: &[u8], message: &[u8]) -> [u8; OUTPUT_LEN] {
+    let mut key_block = [0; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        let mut hasher = Sha256::new();
+        hasher.input(key);
+        key_block[..OUTPUT_LEN].copy_from_slice(&hasher.result());
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut ipad = [0x36; BLOCK_LEN];
+    let mut opad = [0x5c; BLOCK_LEN];
+    for i in 0..BLOCK_LEN {
+        ipad[i] ^= key_block[i];
+        opad[i] ^= key_block[i];
+    }
+
+    let mut inner = Sha256::new();
+    inner.input(&ipad);
+    inner.input(message);
+    let inner = inner.result();
+
+    let mut outer = Sha256::new();
+    outer.input(&opad);
+    outer.input(&inner);
+    outer.result()
+}
+
+#[cfg(test)]
+mod tests {
+    use sha256::{hmac, Sha256};
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn test_hash(tests: &[(&[u8], &str)]) {
+        // Test that it works when accepting the message all at once
+        for &(input, output) in tests {
+            let mut sh = Sha256::new();
+            sh.input(input);
+            assert_eq!(to_hex(&sh.result()), output);
+        }
+
+        // Test that it works when accepting the message in pieces
+        for &(input, output) in tests {
+            let mut sh = Sha256::new();
+            let mut left = input;
+            while !left.is_empty() {
+                let take = (left.len() + 1) / 2;
+                sh.input(&left[..take]);
+                left = &left[take..];
+            }
+            assert_eq!(to_hex(&sh.result()), output);
+        }
+    }
+
+    #[test]
+    fn test_sha256() {
+        let million_a = vec![b'a'; 1000000];
+
+        // Examples from FIPS 180-2
+        let tests: Vec<(&[u8], &str)> = vec![
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+             "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+            (&million_a, "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"),
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        ];
+
+        test_hash(&tests);
+    }
+
+    #[test]
+    fn test_hmac() {
+        let key_4: Vec<u8> = (1..26).collect();
+
+        // Test cases from RFC 4231, except for the truncated output of case 5
+        let tests: Vec<(&[u8], &[u8], &str)> = vec![
+            (&[0x0b; 20], b"Hi There",
+             "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            (b"Jefe", b"what do ya want for nothing?",
+             "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+            (&[0xaa; 20], &[0xdd; 50],
+             "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+            (&key_4, &[0xcd; 50],
+             "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"),
+            (&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First",
+             "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+            (&[0xaa; 131],
+             b"This is a test using a larger than block-size key and a larger than \
+               block-size data. The key needs to be hashed before being used by the HMAC \
+               algorithm.",
+             "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2"),
+        ];
+
+        for &(key, data, output) in &tests {
+            assert_eq!(to_hex(&hmac(key, data)), output);
+        }
+    }
+}
@@ -1,20 +1,23 @@
 //! Prepared statements
 
 use std::cell::{Cell, RefMut};
+use std::cmp;
 use std::collections::VecDeque;
 use std::fmt;
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, Read, Write};
 use std::sync::Arc;
+use std::time::Duration;
 
 use error::{Error, DbError};
-use types::{SessionInfo, Type, ToSql, IsNull};
+use types::{SessionInfo, Type, ToSql};
 use message::FrontendMessage::*;
 use message::BackendMessage::*;
 use message::WriteMessage;
 use util;
-use rows::{Rows, LazyRows};
+use rows::{Rows, LazyRows, RowStream};
 use {read_rows, bad_response, Connection, Transaction, StatementInternals, Result, RowsNew};
-use {InnerConnection, SessionInfoNew, LazyRowsNew, DbErrorNew, ColumnNew, StatementInfo};
+use {desynchronized, InnerConnection, SessionInfoNew, LazyRowsNew, RowStreamNew, DbErrorNew,
+     ColumnNew, StatementInfo};
 
 /// A prepared statement.
 pub struct Statement<'conn> {
@@ -58,11 +61,38 @@ impl<'conn> StatementInternals<'conn> for Statement<'conn> {
         self.conn
     }
 
+    fn info(&self) -> &Arc<StatementInfo> {
+        &self.info
+    }
+
     fn into_query(self, params: &[&ToSql]) -> Result<Rows<'conn>> {
         check_desync!(self.conn);
-        self.inner_query("", 0, params)
+        self.conn.conn.borrow_mut().start_timeout(None);
+        let result = self.inner_query("", 0, params);
+        self.conn
+            .conn
+            .borrow_mut()
+            .finish_timeout(result)
             .map(|(buf, _)| Rows::new_owned(self, buf.into_iter().collect()))
     }
+
+    fn into_query_stream(self, params: &[&ToSql]) -> Result<RowStream<'conn>> {
+        check_desync!(self.conn);
+        try!(self.inner_execute("", 0, params));
+        self.conn.conn.borrow_mut().streaming = true;
+        Ok(RowStream::new_owned(self))
+    }
+
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn bind_values(&self,
+                   conn: &InnerConnection,
+                   params: &[&ToSql])
+                   -> Result<Vec<Option<Vec<u8>>>> {
+        self.info.bind_values(params, &conn.parameters)
+    }
 }
 
 impl<'conn> Statement<'conn> {
@@ -79,21 +109,7 @@ impl<'conn> Statement<'conn> {
 
     fn inner_execute(&self, portal_name: &str, row_limit: i32, params: &[&ToSql]) -> Result<()> {
         let mut conn = self.conn.conn.borrow_mut();
-        assert!(self.param_types().len() == params.len(),
-                "expected {} parameters but got {}",
-                self.param_types().len(),
-                params.len());
-        debug!("executing statement {} with parameters: {:?}",
-               self.info.name,
-               params);
-        let mut values = vec![];
-        for (param, ty) in params.iter().zip(self.param_types()) {
-            let mut buf = vec![];
-            match try!(param.to_sql_checked(ty, &mut buf, &SessionInfo::new(&*conn))) {
-                IsNull::Yes => values.push(None),
-                IsNull::No => values.push(Some(buf)),
-            }
-        }
+        let values = try!(self.bind_values(&conn, params));
 
         try!(conn.write_messages(&[Bind {
                                        portal: portal_name,
@@ -165,6 +181,12 @@ impl<'conn> Statement<'conn> {
     /// ```
     pub fn execute(&self, params: &[&ToSql]) -> Result<u64> {
         check_desync!(self.conn);
+        self.conn.conn.borrow_mut().start_timeout(None);
+        let result = self.execute_inner(params);
+        self.conn.conn.borrow_mut().finish_timeout(result)
+    }
+
+    fn execute_inner(&self, params: &[&ToSql]) -> Result<u64> {
         try!(self.inner_execute("", 0, params));
 
         let mut conn = self.conn.conn.borrow_mut();
@@ -236,8 +258,87 @@ impl<'conn> Statement<'conn> {
     /// }
     /// ```
     pub fn query<'a>(&'a self, params: &[&ToSql]) -> Result<Rows<'a>> {
+        self.query_with_timeout(params, None)
+    }
+
+    /// Like `query`, but overrides the connection's query timeout.
+    ///
+    /// If the query has not completed once `timeout` has passed, it will be
+    /// canceled and `Error::Timeout` will be returned. See
+    /// `Connection::set_query_timeout` for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # use postgres::error::Error;
+    /// # use std::time::Duration;
+    /// # let conn = Connection::connect("", SslMode::None).unwrap();
+    /// let stmt = conn.prepare("SELECT * FROM huge_table").unwrap();
+    /// let result = stmt.query_timeout(&[], Duration::from_secs(5));
+    /// match result {
+    ///     Ok(rows) => println!("{} rows", rows.len()),
+    ///     Err(Error::Timeout) => println!("query took too long"),
+    ///     Err(err) => panic!("{}", err),
+    /// }
+    /// ```
+    pub fn query_timeout<'a>(&'a self,
+                             params: &[&ToSql],
+                             timeout: Duration)
+                             -> Result<Rows<'a>> {
+        self.query_with_timeout(params, Some(timeout))
+    }
+
+    fn query_with_timeout<'a>(&'a self,
+                              params: &[&ToSql],
+                              timeout: Option<Duration>)
+                              -> Result<Rows<'a>> {
+        check_desync!(self.conn);
+        self.conn.conn.borrow_mut().start_timeout(timeout);
+        let result = self.inner_query("", 0, params);
+        self.conn
+            .conn
+            .borrow_mut()
+            .finish_timeout(result)
+            .map(|(buf, _)| Rows::new(self, buf.into_iter().collect()))
+    }
+
+    /// Executes the prepared statement, returning an iterator which reads the
+    /// resulting rows from the connection one at a time.
+    ///
+    /// Unlike `lazy_query`, this does not require a transaction. The
+    /// connection cannot be used for anything else until the `RowStream` has
+    /// been consumed or dropped. See the `RowStream` documentation for
+    /// details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of parameters provided does not match the number
+    /// expected.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", SslMode::None).unwrap();
+    /// let stmt = conn.prepare("SELECT id, name FROM huge_table").unwrap();
+    /// for row in stmt.query_stream(&[]).unwrap() {
+    ///     let row = row.unwrap();
+    ///     let id: i32 = row.get("id");
+    ///     let name: String = row.get("name");
+    ///     println!("{}: {}", id, name);
+    /// }
+    /// ```
+    pub fn query_stream<'a>(&'a self, params: &[&ToSql]) -> Result<RowStream<'a>> {
         check_desync!(self.conn);
-        self.inner_query("", 0, params).map(|(buf, _)| Rows::new(self, buf.into_iter().collect()))
+        try!(self.inner_execute("", 0, params));
+        self.conn.conn.borrow_mut().streaming = true;
+        Ok(RowStream::new(self))
     }
 
     /// Executes the prepared statement, returning a lazily loaded iterator
@@ -303,34 +404,11 @@ impl<'conn> Statement<'conn> {
     /// stmt.copy_in(&[], &mut "1\tjohn\n2\tjane\n".as_bytes()).unwrap();
     /// ```
     pub fn copy_in<R: ReadWithInfo>(&self, params: &[&ToSql], r: &mut R) -> Result<u64> {
-        try!(self.inner_execute("", 0, params));
-        let mut conn = self.conn.conn.borrow_mut();
-
-        let (format, column_formats) = match try!(conn.read_message()) {
-            CopyInResponse { format, column_formats } => (format, column_formats),
-            ErrorResponse { fields } => {
-                try!(conn.wait_for_ready());
-                return DbError::new(fields);
-            }
-            _ => {
-                loop {
-                    match try!(conn.read_message()) {
-                        ReadyForQuery { .. } => {
-                            return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
-                                                                "called `copy_in` on a \
-                                                                 non-`COPY FROM STDIN` \
-                                                                 statement")));
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        };
-
+        let (format, column_formats) = try!(self.start_copy_in(params));
         let mut info = CopyInfo {
-            conn: conn,
-            format: Format::from_u16(format as u16),
-            column_formats: column_formats.iter().map(|&f| Format::from_u16(f)).collect(),
+            conn: self.conn.conn.borrow_mut(),
+            format: format,
+            column_formats: column_formats,
         };
 
         let mut buf = [0; 16 * 1024];
@@ -342,106 +420,114 @@ impl<'conn> Statement<'conn> {
                                 info.conn.stream.write_message(&CopyData { data: &buf[..len] }));
                 }
                 Err(err) => {
-                    try!(info.conn.write_messages(&[CopyFail { message: "" }, CopyDone, Sync]));
-                    match try!(info.conn.read_message()) {
-                        ErrorResponse { .. } => {
-                            // expected from the CopyFail
-                        }
-                        _ => {
-                            info.conn.desynchronized = true;
-                            return Err(Error::Io(bad_response()));
-                        }
-                    }
-                    try!(info.conn.wait_for_ready());
+                    try!(fail_copy_in(&mut info.conn));
                     return Err(Error::Io(err));
                 }
             }
         }
 
-        try!(info.conn.write_messages(&[CopyDone, Sync]));
-
-        let num = match try!(info.conn.read_message()) {
-            CommandComplete { tag } => util::parse_update_count(tag),
-            ErrorResponse { fields } => {
-                try!(info.conn.wait_for_ready());
-                return DbError::new(fields);
-            }
-            _ => {
-                info.conn.desynchronized = true;
-                return Err(Error::Io(bad_response()));
-            }
-        };
-
-        try!(info.conn.wait_for_ready());
-        Ok(num)
+        finish_copy_in(&mut info.conn)
     }
 
-    /// Executes a `COPY TO STDOUT` statement, passing the resulting data to
-    /// the provided writer and returning the number of rows received.
+    /// Executes a `COPY FROM STDIN` statement, returning a writer which
+    /// sends the data written to it to the Postgres server.
     ///
-    /// See the [Postgres documentation](http://www.postgresql.org/docs/9.4/static/sql-copy.html)
-    /// for details on the data format.
+    /// As with `copy_in`, it is the caller's responsibility to ensure the data
+    /// uses the proper format. Data is buffered and sent in chunks, and the
+    /// `COPY` is completed by calling `CopyInWriter::finish`. If the writer is
+    /// dropped without being finished, the `COPY` is aborted and no rows are
+    /// added.
     ///
-    /// If the statement is not a `COPY TO STDOUT` statement it will still be
+    /// The connection cannot be used for anything else until the writer has
+    /// been finished or dropped; other operations will return an error.
+    ///
+    /// If the statement is not a `COPY FROM STDIN` statement it will still be
     /// executed and this method will return an error.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// # use postgres::{Connection, SslMode};
+    /// use std::io::Write;
+    ///
     /// # let conn = Connection::connect("", SslMode::None).unwrap();
-    /// conn.batch_execute("
-    ///         CREATE TABLE people (id INT PRIMARY KEY, name VARCHAR);
-    ///         INSERT INTO people (id, name) VALUES (1, 'john'), (2, 'jane');").unwrap();
-    /// let stmt = conn.prepare("COPY people TO STDOUT").unwrap();
-    /// let mut buf = vec![];
-    /// stmt.copy_out(&[], &mut buf).unwrap();
-    /// assert_eq!(buf, b"1\tjohn\n2\tjane\n");
+    /// conn.batch_execute("CREATE TABLE people (id INT PRIMARY KEY, name VARCHAR)").unwrap();
+    /// let stmt = conn.prepare("COPY people FROM STDIN").unwrap();
+    /// let mut writer = stmt.copy_in_writer(&[]).unwrap();
+    /// for (id, name) in vec![(1, "john"), (2, "jane")] {
+    ///     writeln!(writer, "{}\t{}", id, name).unwrap();
+    /// }
+    /// assert_eq!(writer.finish().unwrap(), 2);
     /// ```
-    pub fn copy_out<'a, W: WriteWithInfo>(&'a self, params: &[&ToSql], w: &mut W) -> Result<u64> {
+    pub fn copy_in_writer<'a>(&'a self, params: &[&ToSql]) -> Result<CopyInWriter<'a>> {
+        let (format, column_formats) = try!(self.start_copy_in(params));
+        self.conn.conn.borrow_mut().streaming = true;
+        Ok(CopyInWriter {
+            stmt: self,
+            format: format,
+            column_formats: column_formats,
+            buf: vec![],
+            finished: false,
+        })
+    }
+
+    fn start_copy_in(&self, params: &[&ToSql]) -> Result<(Format, Vec<Format>)> {
         try!(self.inner_execute("", 0, params));
         let mut conn = self.conn.conn.borrow_mut();
 
-        let (format, column_formats) = match try!(conn.read_message()) {
-            CopyOutResponse { format, column_formats } => (format, column_formats),
-            CopyInResponse { .. } => {
-                try!(conn.write_messages(&[CopyFail { message: "" }, CopyDone, Sync]));
-                match try!(conn.read_message()) {
-                    ErrorResponse { .. } => {
-                        // expected from the CopyFail
-                    }
-                    _ => {
-                        conn.desynchronized = true;
-                        return Err(Error::Io(bad_response()));
-                    }
-                }
-                try!(conn.wait_for_ready());
-                return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
-                                                    "called `copy_out` on a non-`COPY TO \
-                                                     STDOUT` statement")));
+        match try!(conn.read_message()) {
+            CopyInResponse { format, column_formats } => {
+                Ok((Format::from_u16(format as u16),
+                    column_formats.iter().map(|&f| Format::from_u16(f)).collect()))
             }
             ErrorResponse { fields } => {
                 try!(conn.wait_for_ready());
-                return DbError::new(fields);
+                DbError::new(fields)
             }
             _ => {
                 loop {
                     match try!(conn.read_message()) {
                         ReadyForQuery { .. } => {
                             return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
-                                                                "called `copy_out` on a \
-                                                                 non-`COPY TO STDOUT` statement")));
+                                                                "called `copy_in` on a \
+                                                                 non-`COPY FROM STDIN` \
+                                                                 statement")));
                         }
                         _ => {}
                     }
                 }
             }
-        };
+        }
+    }
 
+    /// Executes a `COPY TO STDOUT` statement, passing the resulting data to
+    /// the provided writer and returning the number of rows received.
+    ///
+    /// See the [Postgres documentation](http://www.postgresql.org/docs/9.4/static/sql-copy.html)
+    /// for details on the data format.
+    ///
+    /// If the statement is not a `COPY TO STDOUT` statement it will still be
+    /// executed and this method will return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", SslMode::None).unwrap();
+    /// conn.batch_execute("
+    ///         CREATE TABLE people (id INT PRIMARY KEY, name VARCHAR);
+    ///         INSERT INTO people (id, name) VALUES (1, 'john'), (2, 'jane');").unwrap();
+    /// let stmt = conn.prepare("COPY people TO STDOUT").unwrap();
+    /// let mut buf = vec![];
+    /// stmt.copy_out(&[], &mut buf).unwrap();
+    /// assert_eq!(buf, b"1\tjohn\n2\tjane\n");
+    /// ```
+    pub fn copy_out<'a, W: WriteWithInfo>(&'a self, params: &[&ToSql], w: &mut W) -> Result<u64> {
+        let (format, column_formats) = try!(self.start_copy_out(params));
         let mut info = CopyInfo {
-            conn: conn,
-            format: Format::from_u16(format as u16),
-            column_formats: column_formats.iter().map(|&f| Format::from_u16(f)).collect(),
+            conn: self.conn.conn.borrow_mut(),
+            format: format,
+            column_formats: column_formats,
         };
 
         let count;
@@ -491,6 +577,79 @@ impl<'conn> Statement<'conn> {
         Ok(count)
     }
 
+    /// Executes a `COPY TO STDOUT` statement, returning a reader over the
+    /// resulting data.
+    ///
+    /// The data is read from the server as the reader is consumed. The
+    /// connection cannot be used for anything else until the reader has been
+    /// consumed or dropped; other operations will return an error. If the
+    /// reader is dropped before it has been consumed, the remaining data is
+    /// read and discarded.
+    ///
+    /// If the statement is not a `COPY TO STDOUT` statement it will still be
+    /// executed and this method will return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// use std::io::Read;
+    ///
+    /// # let conn = Connection::connect("", SslMode::None).unwrap();
+    /// let stmt = conn.prepare("COPY people TO STDOUT").unwrap();
+    /// let mut reader = stmt.copy_out_reader(&[]).unwrap();
+    /// let mut buf = String::new();
+    /// reader.read_to_string(&mut buf).unwrap();
+    /// assert_eq!(reader.finish().unwrap(), 2);
+    /// ```
+    pub fn copy_out_reader<'a>(&'a self, params: &[&ToSql]) -> Result<CopyOutReader<'a>> {
+        let (format, column_formats) = try!(self.start_copy_out(params));
+        self.conn.conn.borrow_mut().streaming = true;
+        Ok(CopyOutReader {
+            stmt: self,
+            format: format,
+            column_formats: column_formats,
+            buf: vec![],
+            pos: 0,
+            done: false,
+            count: None,
+        })
+    }
+
+    fn start_copy_out(&self, params: &[&ToSql]) -> Result<(Format, Vec<Format>)> {
+        try!(self.inner_execute("", 0, params));
+        let mut conn = self.conn.conn.borrow_mut();
+
+        match try!(conn.read_message()) {
+            CopyOutResponse { format, column_formats } => {
+                Ok((Format::from_u16(format as u16),
+                    column_formats.iter().map(|&f| Format::from_u16(f)).collect()))
+            }
+            CopyInResponse { .. } => {
+                try!(fail_copy_in(&mut conn));
+                Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                             "called `copy_out` on a non-`COPY TO STDOUT` \
+                                              statement")))
+            }
+            ErrorResponse { fields } => {
+                try!(conn.wait_for_ready());
+                DbError::new(fields)
+            }
+            _ => {
+                loop {
+                    match try!(conn.read_message()) {
+                        ReadyForQuery { .. } => {
+                            return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                                                "called `copy_out` on a \
+                                                                 non-`COPY TO STDOUT` statement")));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
     /// Consumes the statement, clearing it from the Postgres session.
     ///
     /// If this statement was created via the `prepare_cached` method, `finish`
@@ -503,6 +662,40 @@ impl<'conn> Statement<'conn> {
     }
 }
 
+fn finish_copy_in(conn: &mut InnerConnection) -> Result<u64> {
+    try!(conn.write_messages(&[CopyDone, Sync]));
+
+    let num = match try!(conn.read_message()) {
+        CommandComplete { tag } => util::parse_update_count(tag),
+        ErrorResponse { fields } => {
+            try!(conn.wait_for_ready());
+            return DbError::new(fields);
+        }
+        _ => {
+            conn.desynchronized = true;
+            return Err(Error::Io(bad_response()));
+        }
+    };
+
+    try!(conn.wait_for_ready());
+    Ok(num)
+}
+
+fn fail_copy_in(conn: &mut InnerConnection) -> Result<()> {
+    try!(conn.write_messages(&[CopyFail { message: "" }, CopyDone, Sync]));
+    match try!(conn.read_message()) {
+        ErrorResponse { .. } => {
+            // expected from the CopyFail
+        }
+        _ => {
+            conn.desynchronized = true;
+            return Err(Error::Io(bad_response()));
+        }
+    }
+    try!(conn.wait_for_ready());
+    Ok(())
+}
+
 fn fill_copy_buf<R: ReadWithInfo>(buf: &mut [u8], r: &mut R, info: &CopyInfo) -> io::Result<usize> {
     let mut nread = 0;
     while nread < buf.len() {
@@ -564,7 +757,230 @@ impl<'a> CopyInfo<'a> {
 
     /// Returns session info for the associated connection.
     pub fn session_info<'b>(&'b self) -> SessionInfo<'b> {
-        SessionInfo::new(&*self.conn)
+        SessionInfo::new(&self.conn.parameters)
+    }
+}
+
+/// A writer which sends data to the Postgres server as part of a
+/// `COPY FROM STDIN` statement.
+///
+/// Returned by `Statement::copy_in_writer`. If the writer is dropped without
+/// being finished, the `COPY` is aborted.
+pub struct CopyInWriter<'a> {
+    stmt: &'a Statement<'a>,
+    format: Format,
+    column_formats: Vec<Format>,
+    buf: Vec<u8>,
+    finished: bool,
+}
+
+impl<'a> fmt::Debug for CopyInWriter<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CopyInWriter")
+           .field("statement", self.stmt)
+           .field("format", &self.format)
+           .field("column_formats", &self.column_formats)
+           .finish()
+    }
+}
+
+impl<'a> Drop for CopyInWriter<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let mut conn = self.stmt.conn.conn.borrow_mut();
+            conn.streaming = false;
+            if !conn.is_desynchronized() {
+                let _ = fail_copy_in(&mut conn);
+            }
+        }
+    }
+}
+
+impl<'a> CopyInWriter<'a> {
+    /// Returns the format of the overall data.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the format of the individual columns.
+    pub fn column_formats(&self) -> &[Format] {
+        &self.column_formats
+    }
+
+    /// Completes the `COPY`, returning the number of rows added.
+    pub fn finish(mut self) -> Result<u64> {
+        self.finished = true;
+        let result = self.send_buf();
+        let mut conn = self.stmt.conn.conn.borrow_mut();
+        conn.streaming = false;
+        try!(result);
+        check_desync!(conn);
+        finish_copy_in(&mut conn)
+    }
+
+    fn send_buf(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = self.stmt.conn.conn.borrow_mut();
+        if conn.is_desynchronized() {
+            return Err(desynchronized());
+        }
+        try_desync!(conn, conn.stream.write_message(&CopyData { data: &self.buf }));
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<'a> Write for CopyInWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= 16 * 1024 {
+            try!(self.send_buf());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.send_buf());
+        let mut conn = self.stmt.conn.conn.borrow_mut();
+        Ok(try_desync!(conn, conn.stream.flush()))
+    }
+}
+
+/// A reader over the data returned by the Postgres server for a
+/// `COPY TO STDOUT` statement.
+///
+/// Returned by `Statement::copy_out_reader`. If the reader is dropped before
+/// it has been consumed, the remaining data is read and discarded.
+pub struct CopyOutReader<'a> {
+    stmt: &'a Statement<'a>,
+    format: Format,
+    column_formats: Vec<Format>,
+    buf: Vec<u8>,
+    pos: usize,
+    done: bool,
+    count: Option<u64>,
+}
+
+impl<'a> fmt::Debug for CopyOutReader<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CopyOutReader")
+           .field("statement", self.stmt)
+           .field("format", &self.format)
+           .field("column_formats", &self.column_formats)
+           .field("done", &self.done)
+           .finish()
+    }
+}
+
+impl<'a> Drop for CopyOutReader<'a> {
+    fn drop(&mut self) {
+        let _ = self.finish_inner();
+    }
+}
+
+impl<'a> CopyOutReader<'a> {
+    /// Returns the format of the overall data.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the format of the individual columns.
+    pub fn column_formats(&self) -> &[Format] {
+        &self.column_formats
+    }
+
+    /// Consumes the `CopyOutReader`, discarding any remaining data and
+    /// returning the number of rows copied.
+    ///
+    /// Functionally identical to the `Drop` implementation on
+    /// `CopyOutReader` except that it returns any error to the caller.
+    pub fn finish(mut self) -> Result<u64> {
+        self.finish_inner()
+    }
+
+    fn finish_inner(&mut self) -> Result<u64> {
+        while !self.done {
+            self.pos = self.buf.len();
+            try!(self.read_data());
+        }
+
+        match self.count {
+            Some(count) => Ok(count),
+            None => Err(Error::Io(io::Error::new(io::ErrorKind::Other, "the `COPY` failed"))),
+        }
+    }
+
+    fn read_data(&mut self) -> io::Result<()> {
+        let mut conn = self.stmt.conn.conn.borrow_mut();
+        while self.pos == self.buf.len() && !self.done {
+            if conn.is_desynchronized() {
+                conn.streaming = false;
+                self.done = true;
+                return Err(desynchronized());
+            }
+
+            let message = match conn.read_message() {
+                Ok(message) => message,
+                Err(err) => {
+                    conn.streaming = false;
+                    self.done = true;
+                    return Err(err);
+                }
+            };
+
+            match message {
+                BCopyData { data } => {
+                    self.buf = data;
+                    self.pos = 0;
+                }
+                BCopyDone => {}
+                CommandComplete { tag } => {
+                    conn.streaming = false;
+                    self.done = true;
+                    try!(conn.wait_for_ready());
+                    self.count = Some(util::parse_update_count(tag));
+                }
+                ErrorResponse { fields } => {
+                    conn.streaming = false;
+                    self.done = true;
+                    try!(conn.wait_for_ready());
+                    let err: Result<()> = DbError::new(fields);
+                    return err.map_err(From::from);
+                }
+                _ => {
+                    conn.streaming = false;
+                    conn.desynchronized = true;
+                    self.done = true;
+                    return Err(bad_response());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Read for CopyOutReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = {
+            let mut data = try!(self.fill_buf());
+            try!(data.read(buf))
+        };
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<'a> BufRead for CopyOutReader<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        try!(self.read_data());
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.buf.len());
     }
 }
 
@@ -8,7 +8,7 @@ use std::sync::Arc;
 use byteorder::{ReadBytesExt, WriteBytesExt, BigEndian};
 
 pub use self::slice::Slice;
-use {Result, SessionInfoNew, InnerConnection, OtherNew, WrongTypeNew};
+use {Result, SessionInfoNew, OtherNew, WrongTypeNew};
 use error::Error;
 use util;
 
@@ -70,12 +70,12 @@ mod chrono;
 
 /// A structure providing information for conversion methods.
 pub struct SessionInfo<'a> {
-    conn: &'a InnerConnection,
+    parameters: &'a HashMap<String, String>,
 }
 
 impl<'a> SessionInfoNew<'a> for SessionInfo<'a> {
-    fn new(conn: &'a InnerConnection) -> SessionInfo<'a> {
-        SessionInfo { conn: conn }
+    fn new(parameters: &'a HashMap<String, String>) -> SessionInfo<'a> {
+        SessionInfo { parameters: parameters }
     }
 }
 
@@ -83,14 +83,14 @@ impl<'a> SessionInfo<'a> {
     /// Returns the value of the specified Postgres backend parameter, such
     /// as `timezone` or `server_version`.
     pub fn parameter(&self, param: &str) -> Option<&'a str> {
-        self.conn.parameters.get(param).map(|s| &**s)
+        self.parameters.get(param).map(|s| &**s)
     }
 }
 
 impl<'a> fmt::Debug for SessionInfo<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("SessionInfo")
-           .field("parameters", &self.conn.parameters)
+           .field("parameters", self.parameters)
            .finish()
     }
 }
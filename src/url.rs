@@ -1,476 +1,237 @@
-// Copyright 2012-2014 The Rust Project Developers. See the COPYRIGHT
-// file at the top-level directory of this distribution and at
-// http://rust-lang.org/COPYRIGHT.
-//
-// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
-// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
-// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
-// option. This file may not be copied, modified, or distributed
-// except according to those terms.
-use std::io::prelude::*;
-use std::str::FromStr;
-use std::str;
-use hex::FromHex;
+//! Support for libpq-style connection URIs.
+//!
+//! See the [Postgres documentation](http://www.postgresql.org/docs/current/static/libpq-connect.html#LIBPQ-CONNSTRING)
+//! for details on the format.
+pub type DecodeResult<T> = Result<T, String>;
 
-pub struct Url {
-    pub scheme: String,
-    pub user: Option<UserInfo>,
-    pub host: String,
-    pub port: Option<u16>,
-    pub path: Path,
+/// Returns if `s` should be parsed as a URI rather than a keyword/value
+/// connection string.
+pub fn is_uri(s: &str) -> bool {
+    strip_scheme(s).is_some()
 }
 
-pub struct Path {
-    pub path: String,
-    pub query: Query,
-    pub fragment: Option<String>,
-}
+/// Parses a URI of the form
+/// `postgresql://[user[:password]@][host][:port][,...][/dbname][?param=value[&...]]`
+/// into the equivalent keyword/value pairs.
+pub fn parse(s: &str) -> DecodeResult<Vec<(String, String)>> {
+    let rest = match strip_scheme(s) {
+        Some(rest) => rest,
+        None => return Err("URI must start with `postgresql://` or `postgres://`".to_owned()),
+    };
 
-pub struct UserInfo {
-    pub user: String,
-    pub pass: Option<String>,
-}
+    let (rest, query) = match rest.find('?') {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    };
 
-pub type Query = Vec<(String, String)>;
+    let (authority, dbname) = match rest.find('/') {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    };
 
-impl Url {
-    pub fn new(scheme: String,
-               user: Option<UserInfo>,
-               host: String,
-               port: Option<u16>,
-               path: String,
-               query: Query,
-               fragment: Option<String>)
-               -> Url {
-        Url {
-            scheme: scheme,
-            user: user,
-            host: host,
-            port: port,
-            path: Path::new(path, query, fragment),
+    let mut pairs = vec![];
+
+    let hostspec = match authority.find('@') {
+        Some(i) => {
+            let userspec = &authority[..i];
+            let (user, password) = match userspec.find(':') {
+                Some(i) => (&userspec[..i], Some(&userspec[i + 1..])),
+                None => (userspec, None),
+            };
+            if !user.is_empty() {
+                pairs.push(("user".to_owned(), try!(decode(user))));
+            }
+            if let Some(password) = password {
+                pairs.push(("password".to_owned(), try!(decode(password))));
+            }
+            &authority[i + 1..]
         }
-    }
-
-    pub fn parse(rawurl: &str) -> DecodeResult<Url> {
-        // scheme
-        let (scheme, rest) = try!(get_scheme(rawurl));
-
-        // authority
-        let (userinfo, host, port, rest) = try!(get_authority(rest));
-
-        // path
-        let has_authority = host.len() > 0;
-        let (path, rest) = try!(get_path(rest, has_authority));
-
-        // query and fragment
-        let (query, fragment) = try!(get_query_fragment(rest));
+        None => authority,
+    };
 
-        let url = Url::new(scheme.to_owned(),
-                           userinfo,
-                           host.to_owned(),
-                           port,
-                           path,
-                           query,
-                           fragment);
-        Ok(url)
+    let mut hosts = vec![];
+    let mut ports = vec![];
+    for spec in hostspec.split(',') {
+        let (host, port) = try!(split_host_port(spec));
+        hosts.push(try!(decode(host)));
+        ports.push(try!(decode(port)));
     }
-}
-
-impl Path {
-    pub fn new(path: String, query: Query, fragment: Option<String>) -> Path {
-        Path {
-            path: path,
-            query: query,
-            fragment: fragment,
-        }
+    if hosts.len() > 1 || !hosts[0].is_empty() {
+        pairs.push(("host".to_owned(), hosts.join(",")));
     }
-
-    pub fn parse(rawpath: &str) -> DecodeResult<Path> {
-        let (path, rest) = try!(get_path(rawpath, false));
-
-        // query and fragment
-        let (query, fragment) = try!(get_query_fragment(&rest));
-
-        Ok(Path {
-            path: path,
-            query: query,
-            fragment: fragment,
-        })
+    if ports.iter().any(|p| !p.is_empty()) {
+        pairs.push(("port".to_owned(), ports.join(",")));
     }
-}
 
-impl UserInfo {
-    #[inline]
-    pub fn new(user: String, pass: Option<String>) -> UserInfo {
-        UserInfo {
-            user: user,
-            pass: pass,
+    if let Some(dbname) = dbname {
+        if !dbname.is_empty() {
+            pairs.push(("dbname".to_owned(), try!(decode(dbname))));
         }
     }
-}
-
-pub type DecodeResult<T> = Result<T, String>;
-
-pub fn decode_component(container: &str) -> DecodeResult<String> {
-    decode_inner(container, false)
-}
 
-fn decode_inner(c: &str, full_url: bool) -> DecodeResult<String> {
-    let mut out = String::new();
-    let mut iter = c.as_bytes().iter().map(|&b| b);
-
-    loop {
-        match iter.next() {
-            Some(b) => {
-                match b as char {
-                    '%' => {
-                        let bytes = match (iter.next(), iter.next()) {
-                            (Some(one), Some(two)) => [one, two],
-                            _ => {
-                                return Err(format!("Malformed input: found '%' without two \
-                                                    trailing bytes"))
-                            }
-                        };
-
-                        // Only decode some characters if full_url:
-                        match Vec::<u8>::from_hex(str::from_utf8(&bytes)
-                                                      .unwrap())
-                                  .unwrap()[0] as char {
-                            // gen-delims:
-                            ':' |
-                            '/' |
-                            '?' |
-                            '#' |
-                            '[' |
-                            ']' |
-                            '@' |
-                            '!' |
-                            '$' |
-                            '&' |
-                            '"' |
-                            '(' |
-                            ')' |
-                            '*' |
-                            '+' |
-                            ',' |
-                            ';' |
-                            '=' if full_url => {
-                                out.push('%');
-                                out.push(bytes[0] as char);
-                                out.push(bytes[1] as char);
-                            }
-
-                            ch => out.push(ch),
-                        }
-                    }
-                    ch => out.push(ch),
+    if let Some(query) = query {
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = match param.find('=') {
+                Some(i) => (&param[..i], &param[i + 1..]),
+                None => {
+                    return Err(format!("missing key/value separator `=` in URI query \
+                                        parameter `{}`",
+                                       param))
                 }
+            };
+            if key.is_empty() {
+                return Err(format!("empty key in URI query parameter `{}`", param));
             }
-            None => return Ok(out),
+            pairs.push((try!(decode(key)), try!(decode(value))));
         }
     }
+
+    Ok(pairs)
 }
 
-fn split_char_first(s: &str, c: char) -> (&str, &str) {
-    let mut iter = s.splitn(2, c);
+/// Formats keyword/value pairs as a URI which `parse` will convert back into
+/// the same pairs.
+///
+/// Hosts are placed in the authority unless any of them is a Unix socket
+/// directory, in which case the hosts and ports are passed as query
+/// parameters.
+pub fn format(pairs: &[(&str, String)]) -> String {
+    let get = |key| pairs.iter().find(|&&(k, _)| k == key).map(|&(_, ref v)| &**v);
+    let hosts = get("host").map_or(vec![], |h| h.split(',').collect());
+    let ports = get("port").map_or(vec![], |p| p.split(',').collect());
+    let in_authority = !hosts.iter().any(|h| h.starts_with('/'));
 
-    match (iter.next(), iter.next()) {
-        (Some(a), Some(b)) => (a, b),
-        (Some(a), None) => (a, ""),
-        (None, _) => unreachable!(),
-    }
-}
+    let mut s = "postgresql://".to_owned();
 
-fn query_from_str(rawquery: &str) -> DecodeResult<Query> {
-    let mut query: Query = vec![];
-    if !rawquery.is_empty() {
-        for p in rawquery.split('&') {
-            let (k, v) = split_char_first(p, '=');
-            query.push((try!(decode_component(k)), try!(decode_component(v))));
+    if let Some(user) = get("user") {
+        s.push_str(&encode(user));
+        if let Some(password) = get("password") {
+            s.push(':');
+            s.push_str(&encode(password));
         }
+        s.push('@');
     }
 
-    Ok(query)
-}
-
-pub fn get_scheme(rawurl: &str) -> DecodeResult<(&str, &str)> {
-    for (i, c) in rawurl.chars().enumerate() {
-        let result = match c {
-            'A'...'Z' |
-            'a'...'z' => continue,
-            '0'...'9' | '+' | '-' | '.' => {
-                if i != 0 {
-                    continue;
-                }
-
-                Err("url: Scheme must begin with a letter.".to_owned())
+    if in_authority {
+        for (i, host) in hosts.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
             }
-            ':' => {
-                if i == 0 {
-                    Err("url: Scheme cannot be empty.".to_owned())
-                } else {
-                    Ok((&rawurl[0..i], &rawurl[i + 1..rawurl.len()]))
+            if host.contains(':') {
+                s.push('[');
+                s.push_str(host);
+                s.push(']');
+            } else {
+                s.push_str(&encode(host));
+            }
+            match ports.get(i) {
+                Some(port) if !port.is_empty() => {
+                    s.push(':');
+                    s.push_str(port);
                 }
+                _ => {}
             }
-            _ => Err("url: Invalid character in scheme.".to_owned()),
-        };
-
-        return result;
-    }
-
-    Err("url: Scheme must be terminated with a colon.".to_owned())
-}
-
-// returns userinfo, host, port, and unparsed part, or an error
-fn get_authority(rawurl: &str) -> DecodeResult<(Option<UserInfo>, &str, Option<u16>, &str)> {
-    enum State {
-        Start, // starting state
-        PassHostPort, // could be in user or port
-        Ip6Port, // either in ipv6 host or port
-        Ip6Host, // are in an ipv6 host
-        InHost, // are in a host - may be ipv6, but don't know yet
-        InPort, // are in port
-    }
-
-    #[derive(Clone, PartialEq)]
-    enum Input {
-        Digit, // all digits
-        Hex, // digits and letters a-f
-        Unreserved, // all other legal characters
+        }
     }
 
-    if !rawurl.starts_with("//") {
-        // there is no authority.
-        return Ok((None, "", None, rawurl));
+    if let Some(dbname) = get("dbname") {
+        s.push('/');
+        s.push_str(&encode(dbname));
     }
 
-    let len = rawurl.len();
-    let mut st = State::Start;
-    let mut input = Input::Digit; // most restricted, start here.
-
-    let mut userinfo = None;
-    let mut host = "";
-    let mut port = None;
-
-    let mut colon_count = 0usize;
-    let mut pos = 0;
-    let mut begin = 2;
-    let mut end = len;
-
-    for (i, c) in rawurl.chars()
-                        .enumerate()
-                        .skip(2) {
-        // deal with input class first
-        match c {
-            '0'...'9' => (),
-            'A'...'F' |
-            'a'...'f' => {
-                if input == Input::Digit {
-                    input = Input::Hex;
-                }
-            }
-            'G'...'Z' |
-            'g'...'z' |
-            '-' |
-            '.' |
-            '_' |
-            '~' |
-            '%' |
-            '&' |
-            '\'' |
-            '(' |
-            ')' |
-            '+' |
-            '!' |
-            '*' |
-            ',' |
-            ';' |
-            '=' => input = Input::Unreserved,
-            ':' | '@' | '?' | '#' | '/' => {
-                // separators, don't change anything
-            }
-            _ => return Err("Illegal character in authority".to_owned()),
+    let mut first = true;
+    for &(key, ref value) in pairs {
+        match key {
+            "user" | "password" | "dbname" => continue,
+            "host" | "port" if in_authority => continue,
+            _ => {}
         }
+        s.push(if first { '?' } else { '&' });
+        first = false;
+        s.push_str(&encode(key));
+        s.push('=');
+        s.push_str(&encode(value));
+    }
 
-        // now process states
-        match c {
-            ':' => {
-                colon_count += 1;
-                match st {
-                    State::Start => {
-                        pos = i;
-                        st = State::PassHostPort;
-                    }
-                    State::PassHostPort => {
-                        // multiple colons means ipv6 address.
-                        if input == Input::Unreserved {
-                            return Err("Illegal characters in IPv6 address.".to_owned());
-                        }
-                        st = State::Ip6Host;
-                    }
-                    State::InHost => {
-                        pos = i;
-                        if input == Input::Unreserved {
-                            // must be port
-                            host = &rawurl[begin..i];
-                            st = State::InPort;
-                        } else {
-                            // can't be sure whether this is an ipv6 address or a port
-                            st = State::Ip6Port;
-                        }
-                    }
-                    State::Ip6Port => {
-                        if input == Input::Unreserved {
-                            return Err("Illegal characters in authority.".to_owned());
-                        }
-                        st = State::Ip6Host;
-                    }
-                    State::Ip6Host => {
-                        if colon_count > 7 {
-                            host = &rawurl[begin..i];
-                            pos = i;
-                            st = State::InPort;
-                        }
-                    }
-                    _ => return Err("Invalid ':' in authority.".to_owned()),
-                }
-                input = Input::Digit; // reset input class
-            }
-
-            '@' => {
-                input = Input::Digit; // reset input class
-                colon_count = 0; // reset count
-                match st {
-                    State::Start => {
-                        let user = try!(decode_component(&rawurl[begin..i]));
-                        userinfo = Some(UserInfo::new(user, None));
-                        st = State::InHost;
-                    }
-                    State::PassHostPort => {
-                        let user = try!(decode_component(&rawurl[begin..pos]));
-                        let pass = try!(decode_component(&rawurl[pos + 1..i]));
-                        userinfo = Some(UserInfo::new(user, Some(pass)));
-                        st = State::InHost;
-                    }
-                    _ => return Err("Invalid '@' in authority.".to_owned()),
-                }
-                begin = i + 1;
-            }
+    s
+}
 
-            '?' | '#' | '/' => {
-                end = i;
-                break;
-            }
-            _ => (),
+fn strip_scheme(s: &str) -> Option<&str> {
+    for scheme in &["postgresql://", "postgres://"] {
+        if s.starts_with(scheme) {
+            return Some(&s[scheme.len()..]);
         }
     }
+    None
+}
 
-    // finish up
-    match st {
-        State::Start => host = &rawurl[begin..end],
-        State::PassHostPort |
-        State::Ip6Port => {
-            if input != Input::Digit {
-                return Err("Non-digit characters in port.".to_owned());
-            }
-            host = &rawurl[begin..pos];
-            port = Some(&rawurl[pos + 1..end]);
+// IPv6 addresses are enclosed in brackets so their colons aren't confused
+// with the port separator.
+fn split_host_port(spec: &str) -> DecodeResult<(&str, &str)> {
+    let (host, rest) = if spec.starts_with('[') {
+        match spec.find(']') {
+            Some(i) => (&spec[1..i], &spec[i + 1..]),
+            None => return Err(format!("missing `]` in IPv6 host address `{}`", spec)),
         }
-        State::Ip6Host |
-        State::InHost => host = &rawurl[begin..end],
-        State::InPort => {
-            if input != Input::Digit {
-                return Err("Non-digit characters in port.".to_owned());
-            }
-            port = Some(&rawurl[pos + 1..end]);
-        }
-    }
-
-    let rest = &rawurl[end..len];
-    // If we have a port string, ensure it parses to u16.
-    let port = match port {
-        None => None,
-        opt => {
-            match opt.and_then(|p| FromStr::from_str(p).ok()) {
-                None => return Err(format!("Failed to parse port: {:?}", port)),
-                opt => opt,
-            }
+    } else {
+        match spec.find(':') {
+            Some(i) => (&spec[..i], &spec[i..]),
+            None => (spec, ""),
         }
     };
 
-    Ok((userinfo, host, port, rest))
-}
-
-
-// returns the path and unparsed part of url, or an error
-fn get_path(rawurl: &str, is_authority: bool) -> DecodeResult<(String, &str)> {
-    let len = rawurl.len();
-    let mut end = len;
-    for (i, c) in rawurl.chars().enumerate() {
-        match c {
-            'A'...'Z' |
-            'a'...'z' |
-            '0'...'9' |
-            '&' |
-            '\'' |
-            '(' |
-            ')' |
-            '.' |
-            '@' |
-            ':' |
-            '%' |
-            '/' |
-            '+' |
-            '!' |
-            '*' |
-            ',' |
-            ';' |
-            '=' |
-            '_' |
-            '-' |
-            '~' => continue,
-            '?' | '#' => {
-                end = i;
-                break;
-            }
-            _ => return Err("Invalid character in path.".to_owned()),
-        }
-    }
-
-    if is_authority && end != 0 && !rawurl.starts_with("/") {
-        Err("Non-empty path must begin with '/' in presence of authority.".to_owned())
+    if rest.is_empty() {
+        Ok((host, ""))
+    } else if rest.starts_with(':') {
+        Ok((host, &rest[1..]))
     } else {
-        Ok((try!(decode_component(&rawurl[0..end])), &rawurl[end..len]))
+        Err(format!("unexpected character after IPv6 host address `{}`", spec))
     }
 }
 
-// returns the parsed query and the fragment, if present
-fn get_query_fragment(rawurl: &str) -> DecodeResult<(Query, Option<String>)> {
-    let (before_fragment, raw_fragment) = split_char_first(rawurl, '#');
+/// Decodes a percent-encoded URI component.
+fn decode(s: &str) -> DecodeResult<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
 
-    // Parse the fragment if available
-    let fragment = match raw_fragment {
-        "" => None,
-        raw => Some(try!(decode_component(raw))),
-    };
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
 
-    match before_fragment.chars().next() {
-        Some('?') => Ok((try!(query_from_str(&before_fragment[1..])), fragment)),
-        None => Ok((vec![], fragment)),
-        _ => Err(format!("Query didn't start with '?': '{}..'", before_fragment)),
+        let byte = match (bytes.next().and_then(hex_value), bytes.next().and_then(hex_value)) {
+            (Some(hi), Some(lo)) => hi << 4 | lo,
+            _ => return Err(format!("invalid percent-encoded token in `{}`", s)),
+        };
+        if byte == 0 {
+            return Err(format!("forbidden value %00 in percent-encoded value `{}`", s));
+        }
+        out.push(byte);
     }
+
+    String::from_utf8(out).map_err(|_| format!("percent-encoded value `{}` is not valid UTF-8", s))
 }
 
-impl FromStr for Url {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Url, String> {
-        Url::parse(s)
+/// Percent-encodes everything but unreserved characters.
+fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
     }
+    out
 }
 
-impl FromStr for Path {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Path, String> {
-        Path::parse(s)
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'...b'9' => Some(b - b'0'),
+        b'a'...b'f' => Some(b - b'a' + 10),
+        b'A'...b'F' => Some(b - b'A' + 10),
+        _ => None,
     }
 }
@@ -1,10 +1,24 @@
+use std::ascii::AsciiExt;
 use std::io;
 use std::io::prelude::*;
 
+use stmt::Column;
+
 pub fn parse_update_count(tag: String) -> u64 {
     tag.split(' ').last().unwrap().parse().unwrap_or(0)
 }
 
+pub fn column_index(columns: &[Column], name: &str) -> Option<usize> {
+    if let Some(idx) = columns.iter().position(|d| d.name() == name) {
+        return Some(idx);
+    };
+
+    // FIXME ASCII-only case insensitivity isn't really the right thing to
+    // do. Postgres itself uses a dubious wrapper around tolower and JDBC
+    // uses the US locale.
+    columns.iter().position(|d| d.name().eq_ignore_ascii_case(name))
+}
+
 pub fn read_all<R: Read>(r: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
     while !buf.is_empty() {
         match r.read(buf) {
@@ -17,3 +31,63 @@ pub fn read_all<R: Read>(r: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
     }
     Ok(())
 }
+
+const BASE64_CHARS: &'static [u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                                          abcdefghijklmnopqrstuvwxyz\
+                                          0123456789+/";
+
+pub fn base64_encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity((input.len() + 2) / 3 * 4);
+    for chunk in input.chunks(3) {
+        let b = [chunk[0],
+                 *chunk.get(1).unwrap_or(&0),
+                 *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn base64_decode(input: &str) -> io::Result<Vec<u8>> {
+    fn invalid() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, "invalid base64")
+    }
+
+    let input = input.as_bytes();
+    if input.len() % 4 != 0 {
+        return Err(invalid());
+    }
+
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    for chunk in input.chunks(4) {
+        let mut n = 0u32;
+        let mut padding = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            let v = match c {
+                b'A'...b'Z' => c - b'A',
+                b'a'...b'z' => c - b'a' + 26,
+                b'0'...b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                b'=' if i >= 2 => {
+                    padding += 1;
+                    0
+                }
+                _ => return Err(invalid()),
+            };
+            if padding > 0 && c != b'=' {
+                return Err(invalid());
+            }
+            n = n << 6 | v as u32;
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Ok(out)
+}
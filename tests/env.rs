@@ -0,0 +1,166 @@
+//! Tests which modify the process's environment.
+//!
+//! They are kept out of the main test binary so that the variables they set
+//! can't affect other tests, and hold `ENV_LOCK` while running since the
+//! tests in a binary run concurrently.
+extern crate postgres;
+
+use std::io::prelude::*;
+use std::sync::{Mutex, MutexGuard};
+
+use postgres::{Connection, IntoConnectParams, SslMode};
+use postgres::error::ConnectError;
+
+macro_rules! or_panic {
+    ($e:expr) => (
+        match $e {
+            Ok(ok) => ok,
+            Err(err) => panic!("{:#?}", err)
+        }
+    )
+}
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+// A test which fails while holding the lock shouldn't fail the others.
+fn lock_env() -> MutexGuard<'static, ()> {
+    ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pgpass() {
+    use std::env;
+    use std::fs::{self, File};
+    use std::os::unix::fs::PermissionsExt;
+
+    let _guard = lock_env();
+    let path = env::temp_dir().join("rust_postgres_test_pgpass");
+    {
+        let mut file = or_panic!(File::create(&path));
+        or_panic!(file.write_all(b"# comment\n\
+                                  127.0.0.1:5432:postgres:\\*:wrong\n\
+                                  127.0.0.1:*:post\\:gres:md5_user:wrong\n\
+                                  127.0.0.1:*:*:md5_user:password\n"));
+    }
+    or_panic!(fs::set_permissions(&path, fs::Permissions::from_mode(0o600)));
+    env::set_var("PGPASSFILE", &path);
+
+    or_panic!(Connection::connect("postgres://md5_user@127.0.0.1/postgres", SslMode::None));
+
+    or_panic!(fs::set_permissions(&path, fs::Permissions::from_mode(0o644)));
+    let ret = Connection::connect("postgres://md5_user@127.0.0.1/postgres", SslMode::None);
+
+    env::remove_var("PGPASSFILE");
+    let _ = fs::remove_file(&path);
+
+    match ret {
+        Err(ConnectError::ConnectParams(..)) => (),
+        Err(err) => panic!("Unexpected error {:?}", err),
+        _ => panic!("Expected error")
+    }
+}
+
+#[test]
+fn test_env_defaults() {
+    use std::env;
+
+    let _guard = lock_env();
+    env::set_var("PGUSER", "postgres");
+    env::set_var("PGDATABASE", "postgres");
+    env::set_var("PGAPPNAME", "rust_postgres_test_env");
+
+    let params = "postgres://localhost".into_connect_params();
+    let params_override = "dbname=foo application_name=bar".into_connect_params();
+    let conn = Connection::connect("postgres://localhost", SslMode::None);
+
+    env::remove_var("PGUSER");
+    env::remove_var("PGDATABASE");
+    env::remove_var("PGAPPNAME");
+
+    let params = or_panic!(params);
+    assert_eq!("postgres", params.user.unwrap().user);
+    assert_eq!(Some("postgres".to_owned()), params.database);
+
+    let params = or_panic!(params_override);
+    assert_eq!(Some("foo".to_owned()), params.database);
+    assert_eq!(vec![("application_name".to_owned(), "bar".to_owned())], params.options);
+
+    let conn = or_panic!(conn);
+    let rows = or_panic!(conn.query("SELECT current_user::TEXT, \
+                                     current_setting('application_name')",
+                                    &[]));
+    assert_eq!("postgres", rows.get(0).get::<_, String>(0));
+    assert_eq!("rust_postgres_test_env", rows.get(0).get::<_, String>(1));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_os_user_default() {
+    use std::env;
+    use std::process::Command;
+
+    let _guard = lock_env();
+    let vars = ["PGUSER", "USER", "LOGNAME"];
+    let saved = vars.iter().map(|var| env::var_os(var)).collect::<Vec<_>>();
+    for var in &vars {
+        env::remove_var(var);
+    }
+
+    let params = "host=localhost".into_connect_params();
+
+    for (var, value) in vars.iter().zip(saved) {
+        if let Some(value) = value {
+            env::set_var(var, value);
+        }
+    }
+
+    let output = or_panic!(Command::new("id").arg("-un").output());
+    let expected = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    assert_eq!(expected, or_panic!(params).user.unwrap().user);
+}
+
+#[test]
+fn test_service_file() {
+    use std::env;
+    use std::fs::{self, File};
+
+    let _guard = lock_env();
+    let path = env::temp_dir().join("rust_postgres_test_pg_service.conf");
+    {
+        let mut file = or_panic!(File::create(&path));
+        or_panic!(file.write_all(b"[rust_postgres_test_other]\n\
+                                   user=nobody\n\
+                                   \n\
+                                   [rust_postgres_test]\n\
+                                   # comment\n\
+                                   host = localhost\n\
+                                   user=postgres\n\
+                                   dbname=asdf\n\
+                                   application_name=rust_postgres_test_service\n\
+                                   \n\
+                                   [rust_postgres_test_nested]\n\
+                                   service=rust_postgres_test\n"));
+    }
+    env::set_var("PGSERVICEFILE", &path);
+
+    let conn = Connection::connect("service=rust_postgres_test dbname=postgres", SslMode::None);
+    let url_conn = Connection::connect("postgres://localhost/postgres?service=rust_postgres_test",
+                                       SslMode::None);
+    let missing = "service=rust_postgres_test_missing".into_connect_params();
+    let nested = "service=rust_postgres_test_nested".into_connect_params();
+
+    env::remove_var("PGSERVICEFILE");
+    let _ = fs::remove_file(&path);
+
+    for conn in vec![or_panic!(conn), or_panic!(url_conn)] {
+        let rows = or_panic!(conn.query("SELECT current_user::TEXT, current_database()::TEXT, \
+                                         current_setting('application_name')",
+                                        &[]));
+        assert_eq!("postgres", rows.get(0).get::<_, String>(0));
+        assert_eq!("postgres", rows.get(0).get::<_, String>(1));
+        assert_eq!("rust_postgres_test_service", rows.get(0).get::<_, String>(2));
+    }
+    assert!(missing.is_err());
+    assert!(nested.is_err());
+}
@@ -0,0 +1,135 @@
+extern crate async_io;
+extern crate futures_lite;
+
+use self::async_io::{block_on, Async};
+use self::futures_lite::StreamExt;
+use std::net::TcpStream;
+
+use postgres::error::Error;
+use postgres::error::SqlState::{SyntaxError, UndefinedTable};
+use postgres::nonblocking::AsyncConnection;
+use postgres::types::Kind;
+
+fn connect() -> AsyncConnection<Async<TcpStream>> {
+    let stream = or_panic!(block_on(Async::<TcpStream>::connect(([127, 0, 0, 1], 5432))));
+    or_panic!(block_on(AsyncConnection::connect(stream, "postgres://postgres@localhost")))
+}
+
+#[test]
+fn test_async_query() {
+    let mut conn = connect();
+    or_panic!(block_on(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)")));
+    let insert = or_panic!(block_on(conn.prepare("INSERT INTO foo (id, name) VALUES ($1, $2)")));
+    assert_eq!(1, or_panic!(block_on(conn.execute(&insert, &[&1i32, &"joe"]))));
+    assert_eq!(1, or_panic!(block_on(conn.execute(&insert, &[&2i32, &None::<String>]))));
+
+    let select = or_panic!(block_on(conn.prepare("SELECT id, name FROM foo ORDER BY id")));
+    let rows = or_panic!(block_on(conn.query(&select, &[])));
+    assert_eq!(2, rows.len());
+    assert_eq!(1i32, rows[0].get("id"));
+    assert_eq!(Some("joe".to_owned()), rows[0].get(1));
+    assert_eq!(2i32, rows[1].get(0));
+    assert_eq!(None::<String>, rows[1].get("NAME"));
+    or_panic!(block_on(conn.close(select)));
+    or_panic!(block_on(conn.finish()));
+}
+
+#[test]
+fn test_async_unknown_type() {
+    let mut conn = connect();
+    or_panic!(block_on(conn.batch_execute("CREATE TYPE pg_temp.mood AS ENUM ('sad', 'happy')")));
+    let stmt = or_panic!(block_on(conn.prepare("SELECT ARRAY['sad']::pg_temp.mood[]")));
+    let ty = &stmt.columns()[0].type_();
+    assert_eq!("_mood", ty.name());
+    match *ty.kind() {
+        Kind::Array(ref elem) => assert_eq!("mood", elem.name()),
+        ref kind => panic!("unexpected kind {:?}", kind),
+    }
+}
+
+#[test]
+fn test_async_error_recovery() {
+    let mut conn = connect();
+    match block_on(conn.prepare("asdf")) {
+        Err(Error::Db(ref e)) if e.code == SyntaxError => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("unexpected success"),
+    }
+    match block_on(conn.batch_execute("SELECT 1; SELECT * FROM missing_table")) {
+        Err(Error::Db(ref e)) if e.code == UndefinedTable => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("unexpected success"),
+    }
+
+    // The responses to an abandoned request are skipped
+    let stmt = or_panic!(block_on(conn.prepare("SELECT $1::INT")));
+    drop(conn.query(&stmt, &[&1i32]));
+    let rows = or_panic!(block_on(conn.query(&stmt, &[&2i32])));
+    assert_eq!(2i32, rows[0].get(0));
+    assert!(!conn.is_desynchronized());
+}
+
+#[test]
+fn test_async_transaction() {
+    let mut conn = connect();
+    or_panic!(block_on(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)")));
+    let insert = or_panic!(block_on(conn.prepare("INSERT INTO foo (id) VALUES ($1)")));
+
+    {
+        let mut trans = or_panic!(block_on(conn.transaction()));
+        or_panic!(block_on(trans.execute(&insert, &[&1i32])));
+        {
+            let mut nested = or_panic!(block_on(trans.transaction()));
+            assert_eq!(2, nested.transaction_depth());
+            or_panic!(block_on(nested.execute(&insert, &[&2i32])));
+        }
+        or_panic!(block_on(trans.commit()));
+    }
+    {
+        let mut trans = or_panic!(block_on(conn.transaction()));
+        or_panic!(block_on(trans.execute(&insert, &[&3i32])));
+    }
+    assert_eq!(0, conn.transaction_depth());
+
+    let select = or_panic!(block_on(conn.prepare("SELECT id FROM foo ORDER BY id")));
+    let rows = or_panic!(block_on(conn.query(&select, &[])));
+    assert_eq!(vec![1i32], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+}
+
+#[test]
+fn test_async_copy() {
+    let mut conn = connect();
+    or_panic!(block_on(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)")));
+
+    let stmt = or_panic!(block_on(conn.prepare("COPY foo (id, name) FROM STDIN")));
+    let data: &[u8] = b"1\tjoe\n2\tbob\n";
+    assert_eq!(2, or_panic!(block_on(conn.copy_in(&stmt, &[], data))));
+
+    let stmt = or_panic!(block_on(conn.prepare("COPY (SELECT id FROM foo ORDER BY id) TO STDOUT")));
+    let mut buf = vec![];
+    assert_eq!(2, or_panic!(block_on(conn.copy_out(&stmt, &[], &mut buf))));
+    assert_eq!(&b"1\n2\n"[..], &*buf);
+
+    let stmt = or_panic!(block_on(conn.prepare("COPY foo (id) FROM STDIN")));
+    let data: &[u8] = b"asdf\n";
+    match block_on(conn.copy_in(&stmt, &[], data)) {
+        Err(Error::Db(..)) => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("unexpected success"),
+    }
+    let stmt = or_panic!(block_on(conn.prepare("SELECT 1")));
+    or_panic!(block_on(conn.execute(&stmt, &[])));
+}
+
+#[test]
+fn test_async_notifications() {
+    let mut conn = connect();
+    or_panic!(block_on(conn.batch_execute("LISTEN test_async_notifications")));
+
+    let mut other = connect();
+    or_panic!(block_on(other.batch_execute("NOTIFY test_async_notifications, 'foo'")));
+
+    let notification = or_panic!(block_on(conn.notifications().next()).unwrap());
+    assert_eq!("test_async_notifications", notification.channel);
+    assert_eq!("foo", notification.payload);
+}